@@ -1,70 +1,460 @@
-use alloy_dyn_abi::DynSolType;
-use alloy_json_abi::{Function, JsonAbi};
-use alloy_sol_types::decode;
+use crate::{parser::call_output, selectors::SelectorDatabase};
+use alloy_dyn_abi::{DynSolType, DynSolValue};
+use alloy_json_abi::{Event, Function, JsonAbi};
+use reth_primitives::{H160, H256};
 use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
-use revm_primitives::bits::B160;
-use serde_json::Value;
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
 
-pub struct ContractAbiStorage<'a> {
-    mapping: HashMap<&'a B160, PathBuf>,
+/// File inside an ABI directory mapping contract addresses to ABI file names.
+pub const ABI_MANIFEST: &str = "manifest.json";
+
+/// A parsed contract ABI together with selector indexes over its functions and events.
+#[derive(Debug, Clone)]
+pub struct ContractAbi {
+    pub abi: JsonAbi,
+    functions: HashMap<[u8; 4], Function>,
+    events: HashMap<[u8; 32], Event>,
+}
+
+impl ContractAbi {
+    pub fn new(abi: JsonAbi) -> Self {
+        let functions = abi
+            .functions
+            .values()
+            .flatten()
+            .map(|function| (function.selector().into(), function.clone()))
+            .collect();
+
+        let events = abi
+            .events
+            .values()
+            .flatten()
+            .filter(|event| !event.anonymous)
+            .map(|event| (event.selector().into(), event.clone()))
+            .collect();
+
+        Self { abi, functions, events }
+    }
+
+    /// Reads and parses a JSON ABI file.
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let abi: JsonAbi = serde_json::from_reader(reader)?;
+
+        Ok(Self::new(abi))
+    }
+
+    pub fn function(&self, selector: &[u8; 4]) -> Option<&Function> {
+        self.functions.get(selector)
+    }
+
+    /// Looks up a non-anonymous event by its topic0.
+    pub fn event(&self, topic0: &[u8; 32]) -> Option<&Event> {
+        self.events.get(topic0)
+    }
 }
 
-impl<'a> ContractAbiStorage<'a> {
+/// Registry of contract ABIs keyed by contract address.
+///
+/// Each ABI file is parsed once and shared between every address that points to it.
+#[derive(Debug, Clone, Default)]
+pub struct ContractAbiStorage {
+    mapping: HashMap<H160, Arc<ContractAbi>>,
+    selectors: Option<SelectorDatabase>,
+}
+
+impl ContractAbiStorage {
     pub fn new() -> Self {
-        Self { mapping: HashMap::new() }
+        Self { mapping: HashMap::new(), selectors: None }
     }
 
-    pub fn add_abi(&mut self, contract_address: &'a B160, abi_path: PathBuf) {
-        self.mapping.insert(contract_address, abi_path);
+    /// Sets the selector database used for calls to contracts without a registered ABI.
+    pub fn with_selector_database(mut self, selectors: SelectorDatabase) -> Self {
+        self.selectors = Some(selectors);
+        self
     }
 
-    pub fn get_abi(&self, contract_address: &'a B160) -> Option<&PathBuf> {
-        self.mapping.get(contract_address)
+    /// Loads every ABI in `dir`.
+    ///
+    /// Files named after a contract address (`0x<address>.json`) are registered for that
+    /// address, and an optional [`ABI_MANIFEST`] (`{ "<address>": "<file>.json" }`) maps
+    /// addresses to any other file in the directory.
+    pub fn load_dir(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut storage = Self::new();
+        let mut parsed: HashMap<PathBuf, Arc<ContractAbi>> = HashMap::new();
+
+        let mut load = |path: PathBuf| -> Result<Arc<ContractAbi>, Box<dyn std::error::Error>> {
+            if let Some(abi) = parsed.get(&path) {
+                return Ok(abi.clone())
+            }
+            let abi = Arc::new(ContractAbi::from_file(&path)?);
+            parsed.insert(path, abi.clone());
+            Ok(abi)
+        };
+
+        let manifest_path = dir.join(ABI_MANIFEST);
+        if manifest_path.exists() {
+            let reader = BufReader::new(File::open(&manifest_path)?);
+            let manifest: HashMap<String, String> = serde_json::from_reader(reader)?;
+
+            for (address, file) in manifest {
+                let address = parse_address(&address)
+                    .ok_or_else(|| format!("Invalid address {address} in {ABI_MANIFEST}"))?;
+                storage.mapping.insert(address, load(dir.join(file))?);
+            }
+        }
+
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |ext| ext != "json") {
+                continue
+            }
+
+            let address =
+                match path.file_stem().and_then(|stem| stem.to_str()).and_then(parse_address) {
+                    Some(address) => address,
+                    None => continue,
+                };
+            storage.mapping.insert(address, load(path)?);
+        }
+
+        Ok(storage)
     }
+
+    pub fn add_abi(&mut self, contract_address: H160, abi: ContractAbi) {
+        self.mapping.insert(contract_address, Arc::new(abi));
+    }
+
+    pub fn get_abi(&self, contract_address: &H160) -> Option<&ContractAbi> {
+        self.mapping.get(contract_address).map(|abi| abi.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.mapping.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mapping.is_empty()
+    }
+}
+
+/// Parses a `0x`-prefixed or bare hex contract address.
+pub fn parse_address(address: &str) -> Option<H160> {
+    let hex = address.strip_prefix("0x").unwrap_or(address);
+    if hex.len() != 40 {
+        return None
+    }
+
+    H160::from_str(hex).ok()
 }
 
-pub fn sleuth<'a>(
-    storage: &'a ContractAbiStorage,
-    trace: LocalizedTransactionTrace,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let action = trace.trace.action;
+/// A call decoded against the [`Function`] definition found in a contract's ABI.
+#[derive(Debug, Clone)]
+pub struct DecodedCall {
+    pub function_name: String,
+    pub signature: String,
+    pub param_names: Vec<String>,
+    pub params: Vec<DynSolValue>,
+    /// Decoded return values, if the call's output was available and matched the ABI.
+    pub outputs: Option<Vec<DynSolValue>>,
+}
 
-    let (contract_address, input) = match action {
-        RethAction::Call(call_action) => (call_action.to, call_action.input.to_vec()),
+pub fn sleuth(
+    storage: &ContractAbiStorage,
+    trace: &LocalizedTransactionTrace,
+) -> Result<DecodedCall, Box<dyn std::error::Error>> {
+    let (contract_address, input) = match &trace.trace.action {
+        RethAction::Call(call_action) => (call_action.to, &call_action.input[..]),
         _ => return Err(From::from("The action in the transaction trace is not Call(CallAction)")),
     };
 
-    let abi_path = storage.get_abi(&contract_address).ok_or("No ABI found for this contract")?;
+    if input.len() < 4 {
+        return Err(From::from("Calldata is too short to contain a function selector"))
+    }
 
-    let file = std::fs::File::open(abi_path)?;
-    let reader = std::io::BufReader::new(file);
+    let function_selector: [u8; 4] = input[..4].try_into()?;
 
-    let json_abi: JsonAbi = serde_json::from_reader(reader)?;
+    let function = storage
+        .get_abi(&contract_address)
+        .and_then(|contract_abi| contract_abi.function(&function_selector));
 
-    let function_selector = &input[..4];
+    if let Some(function) = function {
+        let mut decoded = decode_call(function, &input[4..])?;
+        decoded.outputs =
+            call_output(trace).and_then(|output| decode_output(function, output).ok());
+        return Ok(decoded)
+    }
 
-    //todo:
-    if let Some(functions) = Some(json_abi.functions.values().flatten()) {
-        for function in functions {
-            if function.selector() == function_selector {
-                let input_types: Vec<String> =
-                    function.inputs.iter().map(|input| input.to_string()).collect();
+    // Without an ABI entry, fall back to the best scoring candidate from the selector database
+    let selectors = storage.selectors.as_ref().ok_or("No ABI found for this contract")?;
 
-                let mut decoded_inputs = Vec::new();
-                for (index, input_type_str) in input_types.iter().enumerate() {
-                    //TODO: just fix this, where you properly provide the expected decoding from the abi 
-                    let input_data = &input[4 + index..]; // Skip the function selector and previous inputs
-                    let dyn_sol_type: DynSolType = input_type_str.parse().unwrap();
-                    let dyn_sol_value = dyn_sol_type.decode_params(input_data)?;
-                    decoded_inputs.push(format!("{:?}", dyn_sol_value));
-                }
+    selectors
+        .decode(input)
+        .into_iter()
+        .next()
+        .map(|candidate| candidate.call)
+        .ok_or_else(|| From::from("No matching function signature found for this selector"))
+}
 
-                let printout = format!("Function: {}\nInputs: {:?}", function.name, decoded_inputs);
-                return Ok(printout)
-            }
+/// Decodes the calldata body (everything after the selector) of a call to `function`.
+///
+/// The inputs are decoded as a single tuple so that dynamic types and their offsets are resolved
+/// against the whole argument block rather than per parameter.
+pub fn decode_call(
+    function: &Function,
+    data: &[u8],
+) -> Result<DecodedCall, Box<dyn std::error::Error>> {
+    let input_types = function
+        .inputs
+        .iter()
+        .map(|param| param.selector_type().parse::<DynSolType>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let params = match DynSolType::Tuple(input_types).decode_params(data)? {
+        DynSolValue::Tuple(values) => values,
+        value => vec![value],
+    };
+
+    Ok(DecodedCall {
+        function_name: function.name.clone(),
+        signature: function.signature(),
+        param_names: function.inputs.iter().map(|param| param.name.clone()).collect(),
+        params,
+        outputs: None,
+    })
+}
+
+/// Decodes the return data of a call to `function`.
+pub fn decode_output(
+    function: &Function,
+    output: &[u8],
+) -> Result<Vec<DynSolValue>, Box<dyn std::error::Error>> {
+    let output_types = function
+        .outputs
+        .iter()
+        .map(|param| param.selector_type().parse::<DynSolType>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match DynSolType::Tuple(output_types).decode_params(output)? {
+        DynSolValue::Tuple(values) => Ok(values),
+        value => Ok(vec![value]),
+    }
+}
+
+/// A log decoded against the [`Event`] definition found in a contract's ABI.
+#[derive(Debug, Clone)]
+pub struct DecodedEvent {
+    pub event_name: String,
+    pub signature: String,
+    pub param_names: Vec<String>,
+    pub params: Vec<DynSolValue>,
+}
+
+/// Decodes a log emitted by `event`.
+///
+/// Indexed parameters are read from `topics[1..]`; indexed parameters of dynamic type are only
+/// stored as their keccak hash, so they are returned as `bytes32`. The remaining parameters are
+/// decoded from `data` as a single tuple.
+pub fn decode_event(
+    event: &Event,
+    topics: &[H256],
+    data: &[u8],
+) -> Result<DecodedEvent, Box<dyn std::error::Error>> {
+    let mut indexed_topics = topics.iter().skip(1);
+
+    let mut body_types = Vec::new();
+    let mut params: Vec<Option<DynSolValue>> = Vec::with_capacity(event.inputs.len());
+
+    for param in &event.inputs {
+        let ty: DynSolType = param.selector_type().parse()?;
+
+        if !param.indexed {
+            body_types.push(ty);
+            params.push(None);
+            continue
         }
+
+        let topic = indexed_topics.next().ok_or("Log has fewer topics than indexed parameters")?;
+        let value = match ty {
+            DynSolType::Address |
+            DynSolType::Bool |
+            DynSolType::Int(_) |
+            DynSolType::Uint(_) |
+            DynSolType::FixedBytes(_) => {
+                match DynSolType::Tuple(vec![ty]).decode_params(&topic[..])? {
+                    DynSolValue::Tuple(mut values) if values.len() == 1 => values.remove(0),
+                    value => value,
+                }
+            }
+            _ => DynSolValue::FixedBytes(topic.0.into(), 32),
+        };
+        params.push(Some(value));
+    }
+
+    let mut body = match DynSolType::Tuple(body_types).decode_params(data)? {
+        DynSolValue::Tuple(values) => values.into_iter(),
+        value => vec![value].into_iter(),
+    };
+
+    let params = params
+        .into_iter()
+        .map(|param| param.or_else(|| body.next()).ok_or("Log data is missing parameters"))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(DecodedEvent {
+        event_name: event.name.clone(),
+        signature: event.signature(),
+        param_names: event.inputs.iter().map(|param| param.name.clone()).collect(),
+        params,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        selectors::signature_selector,
+        test_utils::{address_word, call_trace, returning, reverted, word},
+    };
+    use alloy_primitives::{Address, I256, U256};
+
+    const POOL: &str = "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640";
+    const RECIPIENT: H160 = H160([2; 20]);
+
+    fn abi_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/abi")
     }
 
-    Err(From::from("No matching function found in the ABI"))
+    fn pool() -> H160 {
+        parse_address(POOL).unwrap()
+    }
+
+    /// `swap(RECIPIENT, true, -1000, 0, 0xabcd)` without its selector.
+    fn swap_args() -> Vec<u8> {
+        let mut data = vec![0xab, 0xcd];
+        data.resize(32, 0);
+
+        [address_word(RECIPIENT), word(1), word(-1000), word(0), word(0xa0), word(2)]
+            .concat()
+            .into_iter()
+            .chain(data)
+            .collect()
+    }
+
+    fn swap_function(storage: &ContractAbiStorage) -> &Function {
+        &storage.get_abi(&pool()).unwrap().abi.functions["swap"][0]
+    }
+
+    #[test]
+    fn parses_addresses() {
+        assert_eq!(parse_address(POOL), parse_address(&POOL[2..]));
+        assert_eq!(
+            parse_address("0x0101010101010101010101010101010101010101"),
+            Some(H160([1; 20]))
+        );
+        assert_eq!(parse_address("0x01"), None);
+        assert_eq!(parse_address("0xzz0101010101010101010101010101010101010101"), None);
+    }
+
+    #[test]
+    fn loads_abis_listed_in_the_manifest() {
+        let storage = ContractAbiStorage::load_dir(&abi_dir()).unwrap();
+
+        assert_eq!(storage.len(), 1);
+        let abi = storage.get_abi(&pool()).unwrap();
+        let selector: [u8; 4] = abi.abi.functions["swap"][0].selector().into();
+        assert_eq!(abi.function(&selector).unwrap().name, "swap");
+        assert!(storage.get_abi(&RECIPIENT).is_none());
+    }
+
+    #[test]
+    fn decodes_calls_with_dynamic_params() {
+        let storage = ContractAbiStorage::load_dir(&abi_dir()).unwrap();
+        let decoded = decode_call(swap_function(&storage), &swap_args()).unwrap();
+
+        assert_eq!(decoded.function_name, "swap");
+        assert_eq!(decoded.signature, "swap(address,bool,int256,uint160,bytes)");
+        assert_eq!(
+            decoded.param_names,
+            ["recipient", "zeroForOne", "amountSpecified", "sqrtPriceLimitX96", "data"]
+        );
+        assert_eq!(decoded.params.len(), 5);
+        assert_eq!(decoded.params[0], DynSolValue::Address(Address::from(RECIPIENT.0)));
+        assert_eq!(decoded.params[1], DynSolValue::Bool(true));
+        assert_eq!(decoded.params[4], DynSolValue::Bytes(vec![0xab, 0xcd]));
+    }
+
+    #[test]
+    fn rejects_truncated_calldata() {
+        let storage = ContractAbiStorage::load_dir(&abi_dir()).unwrap();
+        let args = swap_args();
+
+        assert!(decode_call(swap_function(&storage), &args[..args.len() - 32]).is_err());
+    }
+
+    #[test]
+    fn sleuths_calls_to_stored_contracts() {
+        let storage = ContractAbiStorage::load_dir(&abi_dir()).unwrap();
+        let selector: [u8; 4] = swap_function(&storage).selector().into();
+        let input = [&selector[..], &swap_args()[..]].concat();
+
+        let decoded = sleuth(&storage, &call_trace(RECIPIENT, pool(), input.clone())).unwrap();
+        assert_eq!(decoded.function_name, "swap");
+
+        assert!(sleuth(&storage, &call_trace(pool(), RECIPIENT, input)).is_err());
+        assert!(sleuth(&storage, &call_trace(RECIPIENT, pool(), selector[..3].to_vec())).is_err());
+    }
+
+    #[test]
+    fn sleuth_falls_back_to_the_selector_database() {
+        let input = [
+            &signature_selector("transfer(address,uint256)")[..],
+            &address_word(RECIPIENT)[..],
+            &word(100)[..],
+        ]
+        .concat();
+        let trace = call_trace(RECIPIENT, H160([3; 20]), input);
+
+        assert!(sleuth(&ContractAbiStorage::new(), &trace).is_err());
+
+        let mut selectors = SelectorDatabase::new();
+        selectors.insert("transfer(address,uint256)".to_string());
+        let storage = ContractAbiStorage::new().with_selector_database(selectors);
+
+        let decoded = sleuth(&storage, &trace).unwrap();
+        assert_eq!(decoded.function_name, "transfer");
+        assert_eq!(decoded.signature, "transfer(address,uint256)");
+        assert_eq!(decoded.params[0], DynSolValue::Address(Address::from(RECIPIENT.0)));
+    }
+
+    #[test]
+    fn sleuths_return_values() {
+        let storage = ContractAbiStorage::load_dir(&abi_dir()).unwrap();
+        let selector: [u8; 4] = swap_function(&storage).selector().into();
+        let call = call_trace(RECIPIENT, pool(), [&selector[..], &swap_args()[..]].concat());
+
+        let output = [word(1000), word(-500)].concat();
+        let decoded = sleuth(&storage, &returning(call.clone(), output.clone())).unwrap();
+        assert_eq!(
+            decoded.outputs,
+            Some(vec![
+                DynSolValue::Int(I256::from_raw(U256::from(1000)), 256),
+                DynSolValue::Int(-I256::from_raw(U256::from(500)), 256),
+            ])
+        );
+
+        let decoded = sleuth(&storage, &returning(call.clone(), output[..32].to_vec())).unwrap();
+        assert_eq!(decoded.outputs, None);
+
+        let decoded = sleuth(&storage, &reverted(call)).unwrap();
+        assert_eq!(decoded.function_name, "swap");
+        assert_eq!(decoded.outputs, None);
+    }
 }
@@ -1,7 +1,9 @@
+use crate::tokens::TokenMetadata;
 use alloy_primitives::{Address, U256};
 use reth_primitives::{H160, H256};
 use reth_revm::precompile::primitives::ruint::Uint;
 use reth_rpc_types::trace::parity::LocalizedTransactionTrace;
+use std::str::FromStr;
 
 #[derive(Debug, Clone)]
 pub struct Action {
@@ -15,12 +17,32 @@ pub enum ActionType {
     Transfer(Transfer),
     PoolCreation(PoolCreation),
     Swap(Swap),
+    /// Several pool swaps settled by a single call, e.g. a Balancer `batchSwap`.
+    BatchSwap(Vec<Swap>),
+    RouterSwap(RouterSwap),
+    AddLiquidity(AddLiquidity),
+    RemoveLiquidity(RemoveLiquidity),
+    /// Liquidity added to a concentrated liquidity position.
+    Mint(Mint),
+    /// Liquidity removed from a concentrated liquidity position.
+    Burn(Burn),
+    /// Tokens owed to a concentrated liquidity position withdrawn from the pool.
+    Collect(Collect),
+    Sync(ReserveSync),
+    FlashLoan(FlashLoan),
+    Liquidation(Liquidation),
+    /// Several positions liquidated by a single call, e.g. a Compound V3 `absorb`.
+    BatchLiquidation(Vec<Liquidation>),
     WethDeposit(Deposit),
     WethWithdraw(Withdrawal),
+    /// Ether paid directly to the block's fee recipient, usually a searcher's bribe.
+    CoinbaseTransfer(CoinbaseTransfer),
+    /// The builder paying the proposer at the end of the block.
+    ProposerPayment(ProposerPayment),
     Unclassified(LocalizedTransactionTrace),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Protocol {
     UniswapV2,
     Sushiswap,
@@ -34,67 +56,339 @@ pub enum Protocol {
     Dodo,
     DodoV2,
     DodoV3,
+    AaveV2,
+    AaveV3,
+    CompoundV2,
+    CompoundV3,
 }
 
 #[derive(Debug, Clone)]
 pub struct Withdrawal {
     pub to: H160,
     pub amount: alloy_primitives::Uint<256, 4>,
+    /// The WETH contract unwrapped from.
+    pub token: H160,
 }
 
 #[derive(Debug, Clone)]
 pub struct Deposit {
     pub from: H160,
     pub amount: Uint<256, 4>,
+    /// The WETH contract wrapped into.
+    pub token: H160,
 }
 
 #[derive(Debug, Clone)]
 pub struct Transfer {
+    pub from: Address,
     pub to: Address,
     pub amount: U256,
     pub token: H160,
 }
 
+/// A pool deployed by a factory, with its tokens in the order the pool sorts them.
 #[derive(Debug, Clone)]
 pub struct PoolCreation {
+    pub protocol: Protocol,
+    pub factory: H160,
+    /// The deployed pool, `None` if the creation reverted.
+    pub pool: Option<H160>,
     pub token_0: Address,
     pub token_1: Address,
+    /// Swap fee in hundredths of a basis point, as in Uniswap V3.
     pub fee: u32,
 }
 
+/// A swap against a single pool, in the same shape for every protocol. Decoders fill in what
+/// the calldata and return data tell them, and [`TransactionTree::fill_swaps`] completes the
+/// rest from the token transfers of the transaction.
+///
+/// [`TransactionTree::fill_swaps`]: crate::tree::TransactionTree::fill_swaps
 #[derive(Debug, Clone)]
 pub struct Swap {
+    pub protocol: Protocol,
+    /// The pool, or the contract holding its balances for vault-based protocols.
+    pub pool: H160,
+    /// Caller of the pool.
+    pub sender: H160,
     pub recipient: Address,
-    pub direction: bool,
-    pub amount_specified: alloy_primitives::Signed<256, 4>,
-    pub price_limit: alloy_primitives::Uint<256, 4>,
-    pub data: Vec<u8>,
+    /// Token sold to the pool, if known.
+    pub token_in: Option<H160>,
+    /// Token bought from the pool, if known.
+    pub token_out: Option<H160>,
+    /// Amount of `token_in` sold to the pool, if known.
+    pub amount_in: Option<U256>,
+    /// Amount of `token_out` bought from the pool, if known.
+    pub amount_out: Option<U256>,
+}
+
+/// A swap requested through a router, whose pool swaps appear as sub-calls.
+#[derive(Debug, Clone)]
+pub struct RouterSwap {
+    pub protocol: Protocol,
+    pub router: H160,
+    pub path: Vec<Address>,
+    pub recipient: Address,
+    /// The exact input, or the maximum input for exact-output swaps.
+    pub amount_in: U256,
+    /// The minimum output, or the exact output for exact-output swaps.
+    pub amount_out: U256,
+    /// Amounts at each step of the path returned by the router, `None` if it returns nothing.
+    pub amounts: Option<Vec<U256>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AddLiquidity {
+    pub protocol: Protocol,
+    pub pool: H160,
+    pub recipient: Address,
+    /// Token amounts deposited, in pool token order, when known from calldata or return data.
+    pub amounts: Vec<U256>,
+    /// Whether `amounts` are only the most the caller agreed to pay in, e.g. the `maxAmountsIn`
+    /// of a Balancer join, rather than the amounts deposited.
+    pub limits: bool,
+    /// Liquidity (LP tokens) minted, when known.
+    pub liquidity: Option<U256>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoveLiquidity {
+    pub protocol: Protocol,
+    pub pool: H160,
+    pub recipient: Address,
+    /// Token amounts withdrawn, in pool token order, when known from calldata or return data.
+    pub amounts: Vec<U256>,
+    /// Whether `amounts` are only the least the caller agreed to receive, e.g. the
+    /// `minAmountsOut` of a Balancer exit, rather than the amounts withdrawn.
+    pub limits: bool,
+    /// Liquidity (LP tokens) burned, when known.
+    pub liquidity: Option<U256>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FlashLoan {
+    pub protocol: Protocol,
+    pub lender: H160,
+    pub recipient: Address,
+    pub tokens: Vec<H160>,
+    pub amounts: Vec<U256>,
+    /// Fees charged per token, when known.
+    pub fees: Vec<U256>,
+}
+
+/// Repayment of an undercollateralized borrower's debt in exchange for part of their collateral.
+/// Amounts missing from the calldata are completed from the transaction's token transfers by
+/// [`TransactionTree::fill_liquidations`].
+///
+/// [`TransactionTree::fill_liquidations`]: crate::tree::TransactionTree::fill_liquidations
+#[derive(Debug, Clone)]
+pub struct Liquidation {
+    pub protocol: Protocol,
+    /// The lending pool, or the market of the debt for Compound.
+    pub pool: H160,
+    /// Caller repaying the debt, or the absorber for Compound V3.
+    pub liquidator: H160,
+    pub borrower: Address,
+    pub debt_asset: Option<H160>,
+    /// Debt repaid by the liquidator, or absorbed by the protocol for Compound V3. `None` if
+    /// unknown.
+    pub debt_repaid: Option<U256>,
+    /// Collateral seized; the collateral market's cToken for Compound V2.
+    pub collateral_asset: Option<H160>,
+    pub collateral_seized: Option<U256>,
+}
+
+/// A Uniswap V3 style `mint`. The tokens are paid in the mint callback.
+#[derive(Debug, Clone)]
+pub struct Mint {
+    pub protocol: Protocol,
+    pub pool: H160,
+    /// Owner of the position, which is the `recipient` of the mint.
+    pub owner: Address,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    /// Token amounts paid for the liquidity, `None` if the mint reverted.
+    pub amount_0: Option<U256>,
+    pub amount_1: Option<U256>,
+}
+
+/// A Uniswap V3 style `burn`. The tokens are only credited to the position and have to be
+/// withdrawn with a [`Collect`].
+#[derive(Debug, Clone)]
+pub struct Burn {
+    pub protocol: Protocol,
+    pub pool: H160,
+    /// Owner of the position, which is always the caller.
+    pub owner: H160,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    /// Token amounts credited to the position, `None` if the burn reverted.
+    pub amount_0: Option<U256>,
+    pub amount_1: Option<U256>,
+}
+
+/// A Uniswap V3 style `collect` of the tokens owed to a position, i.e. burned liquidity and
+/// accrued fees.
+#[derive(Debug, Clone)]
+pub struct Collect {
+    pub protocol: Protocol,
+    pub pool: H160,
+    /// Owner of the position, which is always the caller.
+    pub owner: H160,
+    pub recipient: Address,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    /// Token amounts collected, or the amounts requested if the collect reverted.
+    pub amount_0: U256,
+    pub amount_1: U256,
+}
+
+/// A forced update of a pool's reserves to its balances.
+#[derive(Debug, Clone)]
+pub struct ReserveSync {
+    pub protocol: Protocol,
+    pub pool: H160,
+}
+
+/// Ether sent to the block's fee recipient by a call inside a transaction.
+#[derive(Debug, Clone)]
+pub struct CoinbaseTransfer {
+    /// The account or contract making the call.
+    pub from: H160,
+    pub fee_recipient: H160,
+    pub amount: U256,
+}
+
+/// The last transaction of a builder's block, sending the block's value on to the proposer.
+#[derive(Debug, Clone)]
+pub struct ProposerPayment {
+    /// The block's fee recipient.
+    pub builder: H160,
+    pub proposer: H160,
+    pub amount: U256,
+}
+
+impl Action {
+    /// Public constructor function to instantiate an [`Action`] decoded from the trace `curr`.
+    pub fn new(ty: ActionType, curr: &LocalizedTransactionTrace) -> Self {
+        Self { ty, hash: curr.transaction_hash.unwrap(), block: curr.block_number.unwrap() }
+    }
+
+    /// The pool swaps this action performed.
+    pub fn swaps(&self) -> &[Swap] {
+        match &self.ty {
+            ActionType::Swap(swap) => std::slice::from_ref(swap),
+            ActionType::BatchSwap(swaps) => swaps,
+            _ => &[],
+        }
+    }
+
+    /// The positions this action liquidated.
+    pub fn liquidations(&self) -> &[Liquidation] {
+        match &self.ty {
+            ActionType::Liquidation(liquidation) => std::slice::from_ref(liquidation),
+            ActionType::BatchLiquidation(liquidations) => liquidations,
+            _ => &[],
+        }
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "UniswapV2" => Ok(Protocol::UniswapV2),
+            "Sushiswap" => Ok(Protocol::Sushiswap),
+            "Balancer" => Ok(Protocol::Balancer),
+            "Curve" => Ok(Protocol::Curve),
+            "UniswapV3" => Ok(Protocol::UniswapV3),
+            "SushiswapV3" => Ok(Protocol::SushiswapV3),
+            "Bancor" => Ok(Protocol::Bancor),
+            "Kyber" => Ok(Protocol::Kyber),
+            "Mooniswap" => Ok(Protocol::Mooniswap),
+            "Dodo" => Ok(Protocol::Dodo),
+            "DodoV2" => Ok(Protocol::DodoV2),
+            "DodoV3" => Ok(Protocol::DodoV3),
+            "AaveV2" => Ok(Protocol::AaveV2),
+            "AaveV3" => Ok(Protocol::AaveV3),
+            "CompoundV2" => Ok(Protocol::CompoundV2),
+            "CompoundV3" => Ok(Protocol::CompoundV3),
+            _ => Err(format!("Unknown protocol {s}")),
+        }
+    }
 }
 
 impl Transfer {
     /// Public constructor function to instantiate a [`Transfer`].
-    pub fn new(to: Address, amount: ruint2::Uint<256, 4>, token: H160) -> Self {
-        Self { to, amount, token }
+    pub fn new(from: Address, to: Address, amount: ruint2::Uint<256, 4>, token: H160) -> Self {
+        Self { from, to, amount, token }
+    }
+
+    /// The transferred amount with the token's decimals and symbol, e.g. `1.5 WETH`.
+    pub fn display_amount(&self, tokens: &TokenMetadata) -> String {
+        tokens.format_amount(&self.token, self.amount)
+    }
+}
+
+impl Swap {
+    /// Public constructor function to instantiate a [`Swap`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        protocol: Protocol,
+        pool: H160,
+        sender: H160,
+        recipient: Address,
+        token_in: Option<H160>,
+        token_out: Option<H160>,
+        amount_in: Option<U256>,
+        amount_out: Option<U256>,
+    ) -> Self {
+        Self { protocol, pool, sender, recipient, token_in, token_out, amount_in, amount_out }
+    }
+
+    /// The swapped amounts with the tokens' decimals and symbols, e.g. `1.5 WETH -> 2800 USDC`.
+    /// Unknown tokens are shown by address and unknown amounts as `?`.
+    pub fn display_amounts(&self, tokens: &TokenMetadata) -> String {
+        let side = |token: Option<H160>, amount: Option<U256>| match (token, amount) {
+            (Some(token), Some(amount)) => tokens.format_amount(&token, amount),
+            (Some(token), None) => format!("? {token:?}"),
+            (None, Some(amount)) => amount.to_string(),
+            (None, None) => "?".to_string(),
+        };
+
+        let amount_in = side(self.token_in, self.amount_in);
+        let amount_out = side(self.token_out, self.amount_out);
+        format!("{amount_in} -> {amount_out}")
     }
 }
 
 impl PoolCreation {
     /// Public constructor function to instantiate a [`PoolCreation`].
-    pub fn new(token_0: Address, token_1: Address, fee: u32) -> Self {
-        Self { token_0, token_1, fee }
+    pub fn new(
+        protocol: Protocol,
+        factory: H160,
+        pool: Option<H160>,
+        token_0: Address,
+        token_1: Address,
+        fee: u32,
+    ) -> Self {
+        Self { protocol, factory, pool, token_0, token_1, fee }
     }
 }
 
 impl Deposit {
     /// Public constructor function to instantiate a [`Deposit`].
-    pub fn new(from: H160, amount: Uint<256, 4>) -> Self {
-        Self { from, amount }
+    pub fn new(from: H160, amount: Uint<256, 4>, token: H160) -> Self {
+        Self { from, amount, token }
     }
 }
 
 impl Withdrawal {
     /// Public constructor function to instantiate a [`Withdrawal`].
-    pub fn new(to: H160, amount: alloy_primitives::Uint<256, 4>) -> Self {
-        Self { to, amount }
+    pub fn new(to: H160, amount: alloy_primitives::Uint<256, 4>, token: H160) -> Self {
+        Self { to, amount, token }
     }
 }
@@ -0,0 +1,300 @@
+use crate::{
+    action::ActionType,
+    parser::to_h160,
+    tree::{ActionNode, TransactionTree},
+    TracingClient,
+};
+use alloy_primitives::{I256, U256};
+use reth_primitives::{H160, H256};
+use reth_rpc_api::EthApiServer;
+use std::collections::HashMap;
+
+/// Key of ether in balance deltas, next to token addresses.
+pub const ETH: H160 = H160([0; 20]);
+
+/// Net balance changes of every account touched by a transaction, computed from what actually
+/// moved rather than from decoded calldata: token transfers, WETH wraps and unwraps and the ether
+/// sent by each call. Changes made in reverted calls are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct BalanceDeltas {
+    /// account → token → change, with ether keyed by [`ETH`].
+    deltas: HashMap<H160, HashMap<H160, I256>>,
+}
+
+impl BalanceDeltas {
+    pub fn new() -> Self {
+        Self { deltas: HashMap::new() }
+    }
+
+    /// Nets the balance changes of a transaction. Gas is paid outside of the call tree and isn't
+    /// included, see [`GasDetails`].
+    pub fn from_tree(tree: &TransactionTree) -> Self {
+        let mut deltas = Self::new();
+        deltas.apply(&tree.root);
+        deltas
+    }
+
+    /// The change of `account`'s balance of `token`.
+    pub fn get(&self, account: &H160, token: &H160) -> I256 {
+        self.deltas.get(account).and_then(|tokens| tokens.get(token)).copied().unwrap_or(I256::ZERO)
+    }
+
+    /// The changes of `account`'s balances by token, skipping tokens it ended up with as much of
+    /// as it started.
+    pub fn account(&self, account: &H160) -> HashMap<H160, I256> {
+        self.net(std::slice::from_ref(account))
+    }
+
+    /// The changes of the combined balances of `accounts` by token, e.g. a searcher and its
+    /// contract, skipping tokens that net to zero.
+    pub fn net(&self, accounts: &[H160]) -> HashMap<H160, I256> {
+        let mut net = HashMap::<H160, I256>::new();
+        for tokens in accounts.iter().filter_map(|account| self.deltas.get(account)) {
+            for (token, delta) in tokens {
+                let total = net.entry(*token).or_insert(I256::ZERO);
+                *total = total.saturating_add(*delta);
+            }
+        }
+
+        net.retain(|_, delta| !delta.is_zero());
+        net
+    }
+
+    fn apply(&mut self, node: &ActionNode) {
+        if node.reverted {
+            return
+        }
+
+        if let Some(value) = &node.value {
+            self.transfer(value.from, value.to, ETH, value.amount);
+        }
+
+        match &node.action.ty {
+            ActionType::Transfer(transfer) => self.transfer(
+                to_h160(&transfer.from),
+                to_h160(&transfer.to),
+                transfer.token,
+                transfer.amount,
+            ),
+            // The ether paid in and out is moved by the deposit call and the withdrawal's refund
+            ActionType::WethDeposit(deposit) => {
+                self.change(deposit.from, deposit.token, saturating_i256(deposit.amount))
+            }
+            ActionType::WethWithdraw(withdrawal) => {
+                self.change(withdrawal.to, withdrawal.token, -saturating_i256(withdrawal.amount))
+            }
+            _ => {}
+        }
+
+        node.absorbed.iter().chain(&node.children).for_each(|child| self.apply(child));
+    }
+
+    fn transfer(&mut self, from: H160, to: H160, token: H160, amount: U256) {
+        let amount = saturating_i256(amount);
+        self.change(from, token, -amount);
+        self.change(to, token, amount);
+    }
+
+    fn change(&mut self, account: H160, token: H160, delta: I256) {
+        let balance = self.deltas.entry(account).or_default().entry(token).or_insert(I256::ZERO);
+        *balance = balance.saturating_add(delta);
+    }
+}
+
+/// Converts a token amount to a balance change. Amounts past `I256::MAX` aren't real token
+/// amounts, so like the sums of changes they saturate instead of being dropped or wrapping.
+fn saturating_i256(amount: U256) -> I256 {
+    I256::try_from(amount).unwrap_or(I256::MAX)
+}
+
+/// What a transaction paid for gas, from its receipt and its block's base fee.
+#[derive(Debug, Clone, Copy)]
+pub struct GasDetails {
+    pub gas_used: u64,
+    pub effective_gas_price: u128,
+    /// The part of the gas price above the base fee, which goes to the fee recipient.
+    pub priority_fee: u128,
+}
+
+impl GasDetails {
+    /// Reads the gas used and gas price of transaction `hash` from its receipt.
+    pub async fn fetch(tracer: &TracingClient, hash: H256, block: u64) -> eyre::Result<Self> {
+        let receipt = EthApiServer::transaction_receipt(&tracer.reth_api, hash)
+            .await
+            .map_err(|err| eyre::eyre!("Failed to fetch the receipt of {hash:?}: {err}"))?
+            .ok_or_else(|| eyre::eyre!("Receipt of {hash:?} not found"))?;
+        let base_fee = tracer.header(block)?.base_fee_per_gas.unwrap_or_default();
+
+        let gas_used = receipt.gas_used.ok_or_else(|| eyre::eyre!("{hash:?} has no gas used"))?;
+        let effective_gas_price = receipt.effective_gas_price.to::<u128>();
+
+        Ok(Self {
+            gas_used: gas_used.to::<u64>(),
+            effective_gas_price,
+            priority_fee: effective_gas_price.saturating_sub(base_fee as u128),
+        })
+    }
+
+    /// The total ether paid for gas.
+    pub fn cost(&self) -> U256 {
+        U256::from(self.gas_used) * U256::from(self.effective_gas_price)
+    }
+
+    /// The ether paid to the fee recipient in priority fees.
+    pub fn priority_fees(&self) -> U256 {
+        U256::from(self.gas_used) * U256::from(self.priority_fee)
+    }
+}
+
+/// The net result of a transaction for its sender and the contract it called.
+#[derive(Debug, Clone)]
+pub struct TransactionPnl {
+    pub transaction: H256,
+    pub searcher: H160,
+    pub contract: Option<H160>,
+    /// Combined balance changes of the searcher and its contract by token, before gas, with ether
+    /// keyed by [`ETH`].
+    pub deltas: HashMap<H160, I256>,
+    pub gas: GasDetails,
+}
+
+impl TransactionPnl {
+    pub fn new(tree: &TransactionTree, gas: GasDetails) -> Self {
+        let accounts = std::iter::once(tree.from).chain(tree.to).collect::<Vec<_>>();
+
+        Self {
+            transaction: tree.hash,
+            searcher: tree.from,
+            contract: tree.to,
+            deltas: BalanceDeltas::from_tree(tree).net(&accounts),
+            gas,
+        }
+    }
+
+    /// Nets the balance changes of `tree` and reads its gas from the receipt.
+    pub async fn fetch(tracer: &TracingClient, tree: &TransactionTree) -> eyre::Result<Self> {
+        let gas = GasDetails::fetch(tracer, tree.hash, tree.block).await?;
+        Ok(Self::new(tree, gas))
+    }
+
+    /// The change of `token` held by the searcher and its contract.
+    pub fn token_delta(&self, token: &H160) -> I256 {
+        self.deltas.get(token).copied().unwrap_or(I256::ZERO)
+    }
+
+    /// The ether the searcher and its contract made, net of gas.
+    pub fn eth_profit(&self) -> I256 {
+        self.token_delta(&ETH).saturating_sub(saturating_i256(self.gas.cost()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        action::{Deposit, Withdrawal},
+        test_utils::{transfer, tree},
+        tree::EthTransfer,
+    };
+
+    const SEARCHER: H160 = H160([1; 20]);
+    const CONTRACT: H160 = H160([2; 20]);
+    const POOL: H160 = H160([3; 20]);
+    const TOKEN: H160 = H160([4; 20]);
+    const WETH: H160 = H160([5; 20]);
+
+    fn delta(value: i64) -> I256 {
+        let delta = I256::from_raw(U256::from(value.unsigned_abs()));
+        if value < 0 {
+            -delta
+        } else {
+            delta
+        }
+    }
+
+    fn block_tree() -> TransactionTree {
+        let mut tree = tree(
+            0,
+            SEARCHER,
+            CONTRACT,
+            vec![
+                ActionType::WethDeposit(Deposit::new(CONTRACT, U256::from(100), WETH)),
+                ActionType::Transfer(transfer(CONTRACT, POOL, WETH, 100)),
+                ActionType::Transfer(transfer(POOL, CONTRACT, TOKEN, 300)),
+                ActionType::Transfer(transfer(CONTRACT, POOL, TOKEN, 50)),
+                ActionType::WethWithdraw(Withdrawal::new(CONTRACT, U256::from(10), WETH)),
+            ],
+        );
+        tree.root.value =
+            Some(EthTransfer { from: SEARCHER, to: CONTRACT, amount: U256::from(100) });
+        // The deposit's ether, and the refund of the withdrawal
+        tree.root.children[0].value =
+            Some(EthTransfer { from: CONTRACT, to: WETH, amount: U256::from(100) });
+        tree.root.children[4].value =
+            Some(EthTransfer { from: WETH, to: CONTRACT, amount: U256::from(10) });
+        tree.root.children[3].reverted = true;
+        tree
+    }
+
+    #[test]
+    fn nets_balance_changes_of_live_calls() {
+        let deltas = BalanceDeltas::from_tree(&block_tree());
+
+        assert_eq!(deltas.get(&SEARCHER, &ETH), delta(-100));
+        assert_eq!(deltas.get(&CONTRACT, &ETH), delta(10));
+        assert_eq!(deltas.get(&CONTRACT, &WETH), delta(-10));
+        assert_eq!(deltas.get(&CONTRACT, &TOKEN), delta(300));
+        assert_eq!(deltas.get(&POOL, &WETH), delta(100));
+        assert_eq!(deltas.get(&POOL, &TOKEN), delta(-300));
+        assert_eq!(deltas.get(&WETH, &ETH), delta(90));
+
+        let searcher = deltas.net(&[SEARCHER, CONTRACT]);
+        assert_eq!(searcher.len(), 3);
+        assert_eq!(searcher[&ETH], delta(-90));
+        assert!(!deltas.account(&POOL).contains_key(&ETH));
+    }
+
+    #[test]
+    fn nets_gas_into_the_ether_profit() {
+        let gas = GasDetails { gas_used: 2, effective_gas_price: 30, priority_fee: 5 };
+        assert_eq!(gas.cost(), U256::from(60));
+        assert_eq!(gas.priority_fees(), U256::from(10));
+
+        let pnl = TransactionPnl::new(&block_tree(), gas);
+
+        assert_eq!(pnl.searcher, SEARCHER);
+        assert_eq!(pnl.contract, Some(CONTRACT));
+        assert_eq!(pnl.token_delta(&TOKEN), delta(300));
+        assert_eq!(pnl.token_delta(&POOL), I256::ZERO);
+        assert_eq!(pnl.eth_profit(), delta(-150));
+    }
+
+    #[test]
+    fn saturates_amounts_past_i256_max() {
+        let mut tree = tree(
+            0,
+            SEARCHER,
+            CONTRACT,
+            vec![
+                ActionType::Transfer(transfer(POOL, CONTRACT, TOKEN, 1)),
+                ActionType::WethDeposit(Deposit::new(CONTRACT, U256::MAX, WETH)),
+            ],
+        );
+        let ActionType::Transfer(sent) = &mut tree.root.children[0].action.ty else {
+            panic!("expected a transfer")
+        };
+        sent.amount = U256::MAX;
+        tree.root.value = Some(EthTransfer { from: CONTRACT, to: POOL, amount: U256::MAX });
+
+        let deltas = BalanceDeltas::from_tree(&tree);
+        assert_eq!(deltas.get(&POOL, &TOKEN), -I256::MAX);
+        assert_eq!(deltas.get(&CONTRACT, &TOKEN), I256::MAX);
+        assert_eq!(deltas.net(&[CONTRACT])[&TOKEN], I256::MAX);
+        assert_eq!(deltas.get(&CONTRACT, &WETH), I256::MAX);
+
+        let gas = GasDetails { gas_used: 2, effective_gas_price: 30, priority_fee: 5 };
+        let pnl = TransactionPnl::new(&tree, gas);
+        assert_eq!(pnl.token_delta(&TOKEN), I256::MAX);
+        assert_eq!(pnl.eth_profit(), I256::MIN);
+    }
+}
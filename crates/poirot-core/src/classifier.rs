@@ -0,0 +1,274 @@
+use crate::{
+    abi::parse_address,
+    action::Protocol,
+    parser::{call_output, to_h160, IUniswapV3Factory, IUniswapV3Pool},
+    TracingClient,
+};
+use alloy_sol_types::SolCall;
+use reth_primitives::H160;
+use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    str::FromStr,
+};
+
+/// What a contract is, which decides the decoders its traces are routed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContractKind {
+    Erc20,
+    Weth,
+    /// A factory deploying pools of the given protocol.
+    Factory(Protocol),
+    Pool(Protocol),
+    /// A periphery contract routing swaps through pools of the given protocol.
+    Router(Protocol),
+    /// A singleton holding the balances of every pool of the given protocol.
+    Vault(Protocol),
+}
+
+/// Well-known mainnet contracts every [`ContractClassifier::mainnet`] table starts with.
+const MAINNET_CONTRACTS: &[(&str, ContractKind)] = &[
+    ("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", ContractKind::Weth),
+    ("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", ContractKind::Erc20),
+    ("0xdac17f958d2ee523a2206206994597c13d831ec7", ContractKind::Erc20),
+    ("0x6b175474e89094c44da98b954eedeac495271d0f", ContractKind::Erc20),
+    ("0x2260fac5e5542a773aa44fbcfedf7c193bc2c599", ContractKind::Erc20),
+    ("0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640", ContractKind::Pool(Protocol::UniswapV3)),
+    ("0x1f98431c8ad98523631ae4a59f267346ea31f984", ContractKind::Factory(Protocol::UniswapV3)),
+    ("0xbaceb8ec6b9355dfc0269c18bac9d6e2bdc29c4f", ContractKind::Factory(Protocol::SushiswapV3)),
+    ("0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f", ContractKind::Factory(Protocol::UniswapV2)),
+    ("0x7a250d5630b4cf539739df2c5dacb4c659f2488d", ContractKind::Router(Protocol::UniswapV2)),
+    ("0xc0aee478e3658e2610c5f7a4a2e1777ce9e4f2ac", ContractKind::Factory(Protocol::Sushiswap)),
+    ("0xd9e1ce17f2641f24ae83637ab66a2cca9c378b9f", ContractKind::Router(Protocol::Sushiswap)),
+    ("0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc", ContractKind::Pool(Protocol::UniswapV2)),
+    ("0x0d4a11d5eeaac28ec3f61d100daf4d40471f1852", ContractKind::Pool(Protocol::UniswapV2)),
+    ("0x397ff1542f962076d0bfe58ea045ffa2d347aca0", ContractKind::Pool(Protocol::Sushiswap)),
+    ("0xbebc44782c7db0a1a60cb6fe97d0b483032ff1c7", ContractKind::Pool(Protocol::Curve)),
+    ("0xd51a44d3fae010294c616388b506acda1bfaae46", ContractKind::Pool(Protocol::Curve)),
+    ("0xba12222222228d8ba445958a75a0704d566bf2c8", ContractKind::Vault(Protocol::Balancer)),
+    ("0xeef417e1d5cc832e619ae18d2f140de2999dd4fb", ContractKind::Router(Protocol::Bancor)),
+    ("0x9aab3f75489902f3a48495025729a0af77d4b11e", ContractKind::Router(Protocol::Kyber)),
+    ("0x0ef1b8a0e726fc3948e15b23993015eb1627f210", ContractKind::Pool(Protocol::Mooniswap)),
+    ("0x75c23271661d9d143dcb617222bc4bec783eff34", ContractKind::Pool(Protocol::Dodo)),
+    ("0xc9f93163c99695c6526b799ebca2207fdf7d61ad", ContractKind::Pool(Protocol::Dodo)),
+    ("0x7d2768de32b0b80b7a3454c06bdac94a69ddc7a9", ContractKind::Pool(Protocol::AaveV2)),
+    ("0x87870bca3f3fd6335c3f4ce8392d69350b4fa4e2", ContractKind::Pool(Protocol::AaveV3)),
+    ("0x4ddc2d193948926d02f9b1fe9e1daa0718270ed5", ContractKind::Pool(Protocol::CompoundV2)),
+    ("0x39aa39c021dfbae8fac545936693ac917d5e7563", ContractKind::Pool(Protocol::CompoundV2)),
+    ("0x5d3a536e4d6dbd6114cc1ead35777bab948e3643", ContractKind::Pool(Protocol::CompoundV2)),
+    ("0xc3d688b66703497daa19211eedff47f25384cdc3", ContractKind::Pool(Protocol::CompoundV3)),
+];
+
+/// Address → [`ContractKind`] table consulted before decoding a trace.
+#[derive(Debug, Clone, Default)]
+pub struct ContractClassifier {
+    contracts: HashMap<H160, ContractKind>,
+}
+
+impl ContractClassifier {
+    pub fn new() -> Self {
+        Self { contracts: HashMap::new() }
+    }
+
+    /// A table seeded with well-known mainnet tokens, pools, factories and routers.
+    pub fn mainnet() -> Self {
+        let mut classifier = Self::new();
+        for (address, kind) in MAINNET_CONTRACTS {
+            classifier.insert(parse_address(address).unwrap(), *kind);
+        }
+        classifier
+    }
+
+    /// Loads a table with one `<address> <kind>` entry per line, where `kind` is `erc20`,
+    /// `weth`, or `<factory|pool|router|vault>:<Protocol>` (e.g. `pool:UniswapV3`). Blank lines
+    /// and lines starting with `#` are skipped.
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut classifier = Self::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+
+            let (address, kind) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| format!("Expected `<address> <kind>`, got {line}"))?;
+            let address =
+                parse_address(address).ok_or_else(|| format!("Invalid address {address}"))?;
+
+            classifier.insert(address, kind.trim().parse()?);
+        }
+
+        Ok(classifier)
+    }
+
+    pub fn insert(&mut self, address: H160, kind: ContractKind) {
+        self.contracts.insert(address, kind);
+    }
+
+    pub fn extend(&mut self, other: ContractClassifier) {
+        self.contracts.extend(other.contracts);
+    }
+
+    pub fn get(&self, address: &H160) -> Option<ContractKind> {
+        self.contracts.get(address).copied()
+    }
+
+    /// Classifies the unknown contracts called with the Uniswap V3 `swap` selector in `traces`
+    /// as pools of the protocol whose factory deployed them, so forks such as SushiswapV3 are
+    /// told apart from Uniswap V3. Pools deployed by a known factory within `traces` are taken
+    /// from the `createPool` call, any other candidate has its `factory()` read at `block`.
+    /// Candidates whose factory isn't classified are left untouched.
+    ///
+    /// Returns the number of pools added to the table.
+    pub async fn resolve_v3_pools(
+        &mut self,
+        tracer: &TracingClient,
+        traces: &[LocalizedTransactionTrace],
+        block: u64,
+    ) -> usize {
+        let mut added = 0;
+        let mut candidates = HashSet::new();
+
+        for trace in traces {
+            let RethAction::Call(call) = &trace.trace.action else { continue };
+            let Some(selector) = call.input.get(..4) else { continue };
+
+            match self.get(&call.to) {
+                Some(ContractKind::Factory(protocol))
+                    if selector == IUniswapV3Factory::createPoolCall::SELECTOR =>
+                {
+                    let Some(created) = call_output(trace).and_then(|output| {
+                        IUniswapV3Factory::createPoolCall::decode_returns(output, true).ok()
+                    }) else {
+                        continue
+                    };
+
+                    self.insert(to_h160(&created._0), ContractKind::Pool(protocol));
+                    added += 1;
+                }
+                None if selector == IUniswapV3Pool::swapCall::SELECTOR => {
+                    candidates.insert(call.to);
+                }
+                _ => {}
+            }
+        }
+
+        for pool in candidates {
+            // Deployed earlier in the block
+            if self.get(&pool).is_some() {
+                continue
+            }
+
+            // Contracts that merely share the selector revert or return garbage
+            let Ok(output) =
+                tracer.eth_call(pool, IUniswapV3Pool::factoryCall {}.encode(), block).await
+            else {
+                continue
+            };
+            let Ok(factory) = IUniswapV3Pool::factoryCall::decode_returns(&output, true) else {
+                continue
+            };
+
+            if let Some(ContractKind::Factory(protocol)) = self.get(&to_h160(&factory._0)) {
+                self.insert(pool, ContractKind::Pool(protocol));
+                added += 1;
+            }
+        }
+
+        added
+    }
+}
+
+impl FromStr for ContractKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s.eq_ignore_ascii_case("erc20") => Ok(ContractKind::Erc20),
+            None if s.eq_ignore_ascii_case("weth") => Ok(ContractKind::Weth),
+            Some((kind, protocol)) => {
+                let protocol = protocol.parse()?;
+                match kind.to_ascii_lowercase().as_str() {
+                    "factory" => Ok(ContractKind::Factory(protocol)),
+                    "pool" => Ok(ContractKind::Pool(protocol)),
+                    "router" => Ok(ContractKind::Router(protocol)),
+                    "vault" => Ok(ContractKind::Vault(protocol)),
+                    _ => Err(format!("Unknown contract kind {s}")),
+                }
+            }
+            None => Err(format!("Unknown contract kind {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_contract_kinds() {
+        assert_eq!("erc20".parse::<ContractKind>(), Ok(ContractKind::Erc20));
+        assert_eq!("WETH".parse::<ContractKind>(), Ok(ContractKind::Weth));
+        assert_eq!(
+            "pool:UniswapV3".parse::<ContractKind>(),
+            Ok(ContractKind::Pool(Protocol::UniswapV3))
+        );
+        assert_eq!(
+            "Factory:Sushiswap".parse::<ContractKind>(),
+            Ok(ContractKind::Factory(Protocol::Sushiswap))
+        );
+
+        assert_eq!(
+            "router:UniswapV2".parse::<ContractKind>(),
+            Ok(ContractKind::Router(Protocol::UniswapV2))
+        );
+
+        assert!("pool".parse::<ContractKind>().is_err());
+        assert!("pool:Uniswap".parse::<ContractKind>().is_err());
+        assert!("oracle:UniswapV2".parse::<ContractKind>().is_err());
+    }
+
+    #[test]
+    fn loads_classification_files() {
+        let path = std::env::temp_dir().join(format!("contracts-{}.txt", std::process::id()));
+        let pool = H160([1; 20]);
+        let token = H160([2; 20]);
+
+        let lines = [
+            "# contracts".to_string(),
+            String::new(),
+            format!("{pool:?} pool:UniswapV3"),
+            format!("{token:?}   erc20"),
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let classifier = ContractClassifier::from_file(&path);
+
+        std::fs::write(&path, format!("{pool:?} pool:UniswapV3\n{token:?}\n")).unwrap();
+        let missing_kind = ContractClassifier::from_file(&path);
+        std::fs::write(&path, format!("{pool:?} router\n")).unwrap();
+        let unknown_kind = ContractClassifier::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let classifier = classifier.unwrap();
+        assert_eq!(classifier.get(&pool), Some(ContractKind::Pool(Protocol::UniswapV3)));
+        assert_eq!(classifier.get(&token), Some(ContractKind::Erc20));
+        assert_eq!(classifier.get(&H160([3; 20])), None);
+        assert!(missing_kind.is_err());
+        assert!(unknown_kind.is_err());
+    }
+
+    #[test]
+    fn seeds_mainnet_contracts() {
+        let classifier = ContractClassifier::mainnet();
+        let weth = parse_address("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2").unwrap();
+
+        assert_eq!(classifier.get(&weth), Some(ContractKind::Weth));
+    }
+}
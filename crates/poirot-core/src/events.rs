@@ -0,0 +1,195 @@
+use crate::{
+    abi::{decode_event, ContractAbiStorage, DecodedEvent},
+    parser::{IComet, IUniswapV2Pair, IUniswapV3Pool, IERC20, WETH9},
+    TracingClient,
+};
+use alloy_sol_types::SolEvent;
+use reth_primitives::{BlockHashOrNumber, Log, Receipt, H160};
+use reth_provider::ReceiptProvider;
+
+/// A decoded log, positioned within its block.
+#[derive(Debug, Clone)]
+pub struct DecodedLog {
+    pub transaction_index: usize,
+    /// Index of the log within the whole block, as reported by `eth_getLogs`.
+    pub log_index: usize,
+    pub address: H160,
+    pub event: LogEvent,
+}
+
+#[derive(Debug, Clone)]
+pub enum LogEvent {
+    Transfer(IERC20::Transfer),
+    WethDeposit(WETH9::Deposit),
+    WethWithdrawal(WETH9::Withdrawal),
+    UniswapV2Swap(IUniswapV2Pair::Swap),
+    UniswapV2Mint(IUniswapV2Pair::Mint),
+    UniswapV2Burn(IUniswapV2Pair::Burn),
+    UniswapV2Sync(IUniswapV2Pair::Sync),
+    UniswapV3Swap(IUniswapV3Pool::Swap),
+    UniswapV3Mint(IUniswapV3Pool::Mint),
+    UniswapV3Burn(IUniswapV3Pool::Burn),
+    CometAbsorbDebt(IComet::AbsorbDebt),
+    CometAbsorbCollateral(IComet::AbsorbCollateral),
+    /// An event decoded through a contract ABI from the [`ContractAbiStorage`].
+    Abi(DecodedEvent),
+}
+
+/// Decodes receipt logs against the `sol!` event interfaces and any loaded contract ABIs.
+pub struct LogDecoder<'a> {
+    abis: &'a ContractAbiStorage,
+}
+
+impl<'a> LogDecoder<'a> {
+    pub fn new(abis: &'a ContractAbiStorage) -> Self {
+        Self { abis }
+    }
+
+    /// Fetches the receipts of `block` from the tracer's provider and decodes their logs.
+    pub fn decode_block(
+        &self,
+        tracer: &TracingClient,
+        block: u64,
+    ) -> eyre::Result<Vec<DecodedLog>> {
+        let receipts = tracer
+            .provider
+            .receipts_by_block(BlockHashOrNumber::Number(block))?
+            .ok_or_else(|| eyre::eyre!("No receipts found for block {block}"))?;
+
+        Ok(self.decode_receipts(&receipts))
+    }
+
+    /// Decodes the logs of a block's receipts, given in transaction order. Logs that match no
+    /// known event are skipped but still count towards the block log index.
+    pub fn decode_receipts(&self, receipts: &[Receipt]) -> Vec<DecodedLog> {
+        receipts
+            .iter()
+            .enumerate()
+            .flat_map(|(transaction_index, receipt)| {
+                receipt.logs.iter().map(move |log| (transaction_index, log))
+            })
+            .enumerate()
+            .filter_map(|(log_index, (transaction_index, log))| {
+                Some(DecodedLog {
+                    transaction_index,
+                    log_index,
+                    address: log.address,
+                    event: self.decode_log(log)?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn decode_log(&self, log: &Log) -> Option<LogEvent> {
+        let topic0 = log.topics.first()?;
+
+        decode_typed(log)
+            .map(LogEvent::Transfer)
+            .or_else(|| decode_typed(log).map(LogEvent::WethDeposit))
+            .or_else(|| decode_typed(log).map(LogEvent::WethWithdrawal))
+            .or_else(|| decode_typed(log).map(LogEvent::UniswapV2Swap))
+            .or_else(|| decode_typed(log).map(LogEvent::UniswapV2Mint))
+            .or_else(|| decode_typed(log).map(LogEvent::UniswapV2Burn))
+            .or_else(|| decode_typed(log).map(LogEvent::UniswapV2Sync))
+            .or_else(|| decode_typed(log).map(LogEvent::UniswapV3Swap))
+            .or_else(|| decode_typed(log).map(LogEvent::UniswapV3Mint))
+            .or_else(|| decode_typed(log).map(LogEvent::UniswapV3Burn))
+            .or_else(|| decode_typed(log).map(LogEvent::CometAbsorbDebt))
+            .or_else(|| decode_typed(log).map(LogEvent::CometAbsorbCollateral))
+            .or_else(|| {
+                let event = self.abis.get_abi(&log.address)?.event(&topic0.0)?;
+                decode_event(event, &log.topics, &log.data).ok().map(LogEvent::Abi)
+            })
+    }
+}
+
+/// Decodes `log` as `E` if its topic0 is the event's signature hash.
+pub(crate) fn decode_typed<E: SolEvent>(log: &Log) -> Option<E> {
+    let topic0 = log.topics.first()?;
+    if topic0[..] != E::SIGNATURE_HASH[..] {
+        return None
+    }
+
+    E::decode_log(log.topics.iter().map(|topic| topic.0), &log.data, true).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        abi::parse_address,
+        test_utils::{address_word, word},
+    };
+    use alloy_dyn_abi::DynSolValue;
+    use alloy_primitives::{Address, I256, U256};
+    use reth_primitives::H256;
+    use std::path::Path;
+
+    const TOKEN: H160 = H160([1; 20]);
+    const FROM: H160 = H160([2; 20]);
+    const TO: H160 = H160([3; 20]);
+
+    fn log(address: H160, topics: Vec<[u8; 32]>, data: Vec<u8>) -> Log {
+        Log { address, topics: topics.into_iter().map(H256).collect(), data: data.into() }
+    }
+
+    fn abis() -> ContractAbiStorage {
+        ContractAbiStorage::load_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src/abi"))
+            .unwrap()
+    }
+
+    #[test]
+    fn decodes_typed_events() {
+        let abis = ContractAbiStorage::new();
+        let topic0 = IERC20::Transfer::SIGNATURE_HASH[..].try_into().unwrap();
+        let transfer =
+            log(TOKEN, vec![topic0, address_word(FROM), address_word(TO)], word(100).to_vec());
+
+        let Some(LogEvent::Transfer(transfer)) = LogDecoder::new(&abis).decode_log(&transfer)
+        else {
+            panic!("expected a transfer")
+        };
+        assert_eq!(transfer.from, Address::from(FROM.0));
+        assert_eq!(transfer.to, Address::from(TO.0));
+        assert_eq!(transfer.value, U256::from(100));
+    }
+
+    #[test]
+    fn decodes_events_of_loaded_abis() {
+        let abis = abis();
+        let pool = parse_address("0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640").unwrap();
+        let topic0 = abis.get_abi(&pool).unwrap().abi.events["Collect"][0].selector().into();
+        let collect = log(
+            pool,
+            vec![topic0, address_word(FROM), word(-600), word(600)],
+            [address_word(TO), word(5), word(7)].concat(),
+        );
+
+        let Some(LogEvent::Abi(collect)) = LogDecoder::new(&abis).decode_log(&collect) else {
+            panic!("expected an ABI event")
+        };
+        assert_eq!(collect.event_name, "Collect");
+        assert_eq!(
+            collect.param_names,
+            ["owner", "recipient", "tickLower", "tickUpper", "amount0", "amount1"]
+        );
+        assert_eq!(collect.params[0], DynSolValue::Address(Address::from(FROM.0)));
+        assert_eq!(collect.params[1], DynSolValue::Address(Address::from(TO.0)));
+        assert_eq!(collect.params[2], DynSolValue::Int(-I256::from_raw(U256::from(600)), 24));
+        assert_eq!(collect.params[5], DynSolValue::Uint(U256::from(7), 128));
+    }
+
+    #[test]
+    fn skips_unknown_events() {
+        let abis = abis();
+        let decoder = LogDecoder::new(&abis);
+
+        assert!(decoder.decode_log(&log(TOKEN, vec![], vec![])).is_none());
+        assert!(decoder.decode_log(&log(TOKEN, vec![[0xee; 32]], word(1).to_vec())).is_none());
+
+        // A known event whose data doesn't decode
+        let topic0 = IERC20::Transfer::SIGNATURE_HASH[..].try_into().unwrap();
+        let truncated = log(TOKEN, vec![topic0, address_word(FROM), address_word(TO)], vec![]);
+        assert!(decoder.decode_log(&truncated).is_none());
+    }
+}
@@ -0,0 +1,35 @@
+use crate::tree::TransactionTree;
+use reth_primitives::H160;
+use std::collections::HashMap;
+
+pub mod arbitrage;
+pub mod cex_dex;
+pub mod jit;
+pub mod liquidation;
+pub mod sandwich;
+
+pub use arbitrage::{Arbitrage, ArbitrageInspector};
+pub use cex_dex::{CexDex, CexDexInspector, CexPrices};
+pub use jit::{JitInspector, JitLiquidity};
+pub use liquidation::{LiquidationInspector, LiquidationReport};
+pub use sandwich::{Sandwich, SandwichInspector, VictimLoss};
+
+/// Looks for a kind of MEV in the parsed transactions of a block.
+pub trait Inspector {
+    type Result;
+
+    /// Inspects the transaction trees of a block, given in block order.
+    fn inspect(&self, trees: &[TransactionTree]) -> Vec<Self::Result>;
+}
+
+/// Token prices used to value amounts of different tokens against each other.
+pub trait PriceSource {
+    /// The value of one raw unit of `token` in raw units of the quote token, if known.
+    fn price(&self, token: &H160) -> Option<f64>;
+}
+
+impl PriceSource for HashMap<H160, f64> {
+    fn price(&self, token: &H160) -> Option<f64> {
+        self.get(token).copied()
+    }
+}
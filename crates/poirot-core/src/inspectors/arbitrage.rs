@@ -0,0 +1,194 @@
+use super::Inspector;
+use crate::{
+    action::{Protocol, Swap},
+    tree::TransactionTree,
+};
+use alloy_primitives::{I256, U256};
+use reth_primitives::{H160, H256};
+
+/// Consecutive swaps of a transaction that start and end with the same token.
+#[derive(Debug, Clone)]
+pub struct Arbitrage {
+    pub block: u64,
+    pub transaction: H256,
+    /// The account that sent the transaction.
+    pub searcher: H160,
+    /// The contract called by the transaction.
+    pub contract: Option<H160>,
+    /// The swaps of the cycle, in execution order.
+    pub route: Vec<Swap>,
+    /// The token the cycle starts and ends with.
+    pub token: H160,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    /// Output minus input, in `token`, before gas and bribes.
+    pub profit: I256,
+}
+
+impl Arbitrage {
+    /// The protocols of the route, in the order they are first swapped on.
+    pub fn protocols(&self) -> Vec<Protocol> {
+        let mut protocols = vec![];
+        for swap in &self.route {
+            if !protocols.contains(&swap.protocol) {
+                protocols.push(swap.protocol);
+            }
+        }
+        protocols
+    }
+}
+
+/// Finds atomic arbitrages: runs of two or more swaps within a transaction where each swap sells
+/// the token bought by the previous one and the last swap buys back the token the first sold.
+/// Swaps made in reverted calls aren't part of any route.
+#[derive(Debug, Clone, Default)]
+pub struct ArbitrageInspector;
+
+impl Inspector for ArbitrageInspector {
+    type Result = Arbitrage;
+
+    fn inspect(&self, trees: &[TransactionTree]) -> Vec<Arbitrage> {
+        trees.iter().flat_map(find_cycles).collect()
+    }
+}
+
+fn find_cycles(tree: &TransactionTree) -> Vec<Arbitrage> {
+    let swaps = tree.swaps();
+    let mut arbitrages = vec![];
+
+    let mut start = 0;
+    while start < swaps.len() {
+        match close_cycle(&swaps[start..]) {
+            Some(len) => {
+                if let Some(arbitrage) = to_arbitrage(tree, &swaps[start..start + len]) {
+                    arbitrages.push(arbitrage);
+                }
+                start += len;
+            }
+            None => start += 1,
+        }
+    }
+
+    arbitrages
+}
+
+/// Returns the length of the shortest cycle of chained swaps starting with the first swap.
+fn close_cycle(swaps: &[&Swap]) -> Option<usize> {
+    let start = swaps.first()?.token_in?;
+
+    let mut token = swaps[0].token_out?;
+    for (index, swap) in swaps.iter().enumerate().skip(1) {
+        if swap.token_in? != token {
+            return None
+        }
+
+        token = swap.token_out?;
+        if token == start {
+            return Some(index + 1)
+        }
+    }
+
+    None
+}
+
+fn to_arbitrage(tree: &TransactionTree, route: &[&Swap]) -> Option<Arbitrage> {
+    let (first, last) = (route.first()?, route.last()?);
+    let amount_in = first.amount_in?;
+    let amount_out = last.amount_out?;
+
+    Some(Arbitrage {
+        block: tree.block,
+        transaction: tree.hash,
+        searcher: tree.from,
+        contract: tree.to,
+        route: route.iter().copied().cloned().collect(),
+        token: first.token_in?,
+        amount_in,
+        amount_out,
+        profit: I256::try_from(amount_out).ok()? - I256::try_from(amount_in).ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        action::ActionType,
+        test_utils::{swap, tree},
+    };
+
+    const SEARCHER: H160 = H160([1; 20]);
+    const CONTRACT: H160 = H160([2; 20]);
+    const TOKEN_A: H160 = H160([3; 20]);
+    const TOKEN_B: H160 = H160([4; 20]);
+    const TOKEN_C: H160 = H160([5; 20]);
+
+    fn hop(pool: u8, token_in: H160, token_out: H160, amounts: (u64, u64)) -> ActionType {
+        ActionType::Swap(swap(H160([pool; 20]), token_in, token_out, amounts.0, amounts.1))
+    }
+
+    #[test]
+    fn finds_multi_hop_cycles() {
+        let trees = [tree(
+            0,
+            SEARCHER,
+            CONTRACT,
+            vec![
+                hop(10, TOKEN_A, TOKEN_B, (100, 200)),
+                hop(11, TOKEN_B, TOKEN_C, (200, 300)),
+                hop(12, TOKEN_C, TOKEN_A, (300, 110)),
+                hop(13, TOKEN_A, TOKEN_B, (5, 10)),
+            ],
+        )];
+
+        let arbitrages = ArbitrageInspector.inspect(&trees);
+
+        assert_eq!(arbitrages.len(), 1);
+        let arbitrage = &arbitrages[0];
+        assert_eq!(arbitrage.transaction, trees[0].hash);
+        assert_eq!(arbitrage.searcher, SEARCHER);
+        assert_eq!(arbitrage.contract, Some(CONTRACT));
+        assert_eq!(arbitrage.route.len(), 3);
+        assert_eq!(arbitrage.route[2].pool, H160([12; 20]));
+        assert_eq!(arbitrage.token, TOKEN_A);
+        assert_eq!(arbitrage.amount_in, U256::from(100));
+        assert_eq!(arbitrage.amount_out, U256::from(110));
+        assert_eq!(arbitrage.profit, I256::from_raw(U256::from(10)));
+        assert_eq!(arbitrage.protocols(), vec![Protocol::UniswapV2]);
+    }
+
+    #[test]
+    fn ignores_swaps_that_dont_close_a_cycle() {
+        let chained = tree(
+            0,
+            SEARCHER,
+            CONTRACT,
+            vec![hop(10, TOKEN_A, TOKEN_B, (100, 200)), hop(11, TOKEN_B, TOKEN_C, (200, 300))],
+        );
+        let broken = tree(
+            1,
+            SEARCHER,
+            CONTRACT,
+            vec![hop(10, TOKEN_A, TOKEN_B, (100, 200)), hop(12, TOKEN_C, TOKEN_A, (300, 110))],
+        );
+
+        assert!(ArbitrageInspector.inspect(&[chained, broken]).is_empty());
+    }
+
+    #[test]
+    fn ignores_cycles_closed_by_a_reverted_hop() {
+        let mut reverted = tree(
+            0,
+            SEARCHER,
+            CONTRACT,
+            vec![
+                hop(10, TOKEN_A, TOKEN_B, (100, 200)),
+                hop(11, TOKEN_B, TOKEN_C, (200, 300)),
+                hop(12, TOKEN_C, TOKEN_A, (300, 110)),
+            ],
+        );
+        reverted.root.children[2].reverted = true;
+
+        assert!(ArbitrageInspector.inspect(&[reverted]).is_empty());
+    }
+}
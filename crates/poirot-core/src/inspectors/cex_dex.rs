@@ -0,0 +1,381 @@
+use super::Inspector;
+use crate::{
+    abi::parse_address, action::Swap, pricing::u256_to_f64, tokens::TokenMetadata,
+    tree::TransactionTree, TracingClient,
+};
+use alloy_primitives::U256;
+use parquet::{
+    file::reader::{FileReader, SerializedFileReader},
+    record::Field,
+};
+use reth_primitives::{H160, H256};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    sync::Arc,
+};
+
+/// How long a centralized exchange price stays usable as the reference for later blocks.
+pub const DEFAULT_MAX_PRICE_AGE: u64 = 60;
+
+/// Centralized exchange prices of token pairs over time, loaded from local trade or quote
+/// exports.
+#[derive(Debug, Clone, Default)]
+pub struct CexPrices {
+    /// `(base, quote)` → `(timestamp, price)` sorted by timestamp.
+    prices: HashMap<(H160, H160), Vec<(u64, f64)>>,
+}
+
+impl CexPrices {
+    pub fn new() -> Self {
+        Self { prices: HashMap::new() }
+    }
+
+    /// Loads a CSV file with one `<timestamp>,<base token>,<quote token>,<price>` row per trade
+    /// or quote, where the price is in whole quote tokens per whole base token and the timestamp
+    /// in seconds since the epoch. A header row and `#` comments are skipped.
+    pub fn from_csv(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut prices = Self::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+
+            match parse_price_line(line) {
+                Some((timestamp, base, quote, price)) => {
+                    prices.insert(base, quote, timestamp, price)
+                }
+                // The first row may be a header
+                None if index == 0 => continue,
+                None => return Err(format!("Invalid price row {line}").into()),
+            }
+        }
+
+        Ok(prices)
+    }
+
+    /// Loads a Parquet file with `timestamp`, `base`, `quote` and `price` columns holding the
+    /// values of a [`CexPrices::from_csv`] row. The timestamp may also be a Parquet timestamp and
+    /// other columns are ignored.
+    pub fn from_parquet(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let reader = SerializedFileReader::new(File::open(path)?)?;
+        let mut prices = Self::new();
+
+        for row in reader.get_row_iter(None)? {
+            let row = row?;
+            let (mut timestamp, mut base, mut quote, mut price) = (None, None, None, None);
+
+            for (name, field) in row.get_column_iter() {
+                match (name.as_str(), field) {
+                    ("timestamp", Field::Long(seconds)) => timestamp = u64::try_from(*seconds).ok(),
+                    ("timestamp", Field::ULong(seconds)) => timestamp = Some(*seconds),
+                    ("timestamp", Field::TimestampMillis(millis)) => {
+                        timestamp = u64::try_from(*millis / 1_000).ok()
+                    }
+                    ("timestamp", Field::TimestampMicros(micros)) => {
+                        timestamp = u64::try_from(*micros / 1_000_000).ok()
+                    }
+                    ("base", Field::Str(address)) => base = parse_address(address),
+                    ("quote", Field::Str(address)) => quote = parse_address(address),
+                    ("price", Field::Double(value)) => price = Some(*value),
+                    ("price", Field::Float(value)) => price = Some(f64::from(*value)),
+                    _ => {}
+                }
+            }
+
+            let (Some(timestamp), Some(base), Some(quote), Some(price)) =
+                (timestamp, base, quote, price)
+            else {
+                return Err(format!("Invalid price row {row}").into())
+            };
+            prices.insert(base, quote, timestamp, price);
+        }
+
+        Ok(prices)
+    }
+
+    pub fn insert(&mut self, base: H160, quote: H160, timestamp: u64, price: f64) {
+        let series = self.prices.entry((base, quote)).or_default();
+        let position = series.partition_point(|(time, _)| *time <= timestamp);
+        series.insert(position, (timestamp, price));
+    }
+
+    /// The last price of `base` in `quote` at or before `timestamp` and at most `max_age` seconds
+    /// old, inverting the prices of the reverse pair if needed.
+    pub fn price_at(&self, base: H160, quote: H160, timestamp: u64, max_age: u64) -> Option<f64> {
+        let latest = |pair: &(H160, H160)| {
+            let series = self.prices.get(pair)?;
+            let position = series.partition_point(|(time, _)| *time <= timestamp);
+            let (time, price) = series[..position].last()?;
+            (timestamp - time <= max_age).then_some(*price)
+        };
+
+        latest(&(base, quote)).or_else(|| {
+            latest(&(quote, base)).filter(|price| *price != 0.0).map(|price| 1.0 / price)
+        })
+    }
+}
+
+/// A swap of a known searcher priced against the centralized exchange price at block time.
+#[derive(Debug, Clone)]
+pub struct CexDex {
+    pub block: u64,
+    pub timestamp: u64,
+    pub transaction: H256,
+    /// The account that sent the transaction.
+    pub searcher: H160,
+    /// The contract called by the transaction.
+    pub contract: Option<H160>,
+    pub swap: Swap,
+    /// Whole output tokens received per whole input token.
+    pub dex_price: f64,
+    /// Whole output tokens per whole input token on the centralized exchange.
+    pub cex_price: f64,
+    /// Output received minus the output the input is worth on the centralized exchange, in whole
+    /// output tokens. Positive when the swap beat the centralized exchange.
+    pub edge: f64,
+}
+
+/// Flags transactions of known searchers making a single pool swap whose price diverges from the
+/// centralized exchange price at block time, the footprint of arbitrage hedged off-chain. Block
+/// timestamps have to be loaded with [`CexDexInspector::load_timestamp`] before inspecting, and
+/// both tokens of a swap need known decimals.
+#[derive(Debug, Clone)]
+pub struct CexDexInspector {
+    prices: Arc<CexPrices>,
+    tokens: Arc<TokenMetadata>,
+    searchers: HashSet<H160>,
+    timestamps: HashMap<u64, u64>,
+    max_price_age: u64,
+    /// Minimum relative difference between the swap and the reference price to report.
+    min_divergence: f64,
+}
+
+impl CexDexInspector {
+    pub fn new(
+        prices: Arc<CexPrices>,
+        tokens: Arc<TokenMetadata>,
+        searchers: HashSet<H160>,
+    ) -> Self {
+        Self {
+            prices,
+            tokens,
+            searchers,
+            timestamps: HashMap::new(),
+            max_price_age: DEFAULT_MAX_PRICE_AGE,
+            min_divergence: 0.0,
+        }
+    }
+
+    pub fn with_max_price_age(mut self, seconds: u64) -> Self {
+        self.max_price_age = seconds;
+        self
+    }
+
+    pub fn with_min_divergence(mut self, divergence: f64) -> Self {
+        self.min_divergence = divergence;
+        self
+    }
+
+    /// Reads the timestamp of `block` from its header.
+    pub fn load_timestamp(&mut self, tracer: &TracingClient, block: u64) -> eyre::Result<u64> {
+        let timestamp = tracer.block_timestamp(block)?;
+        self.timestamps.insert(block, timestamp);
+        Ok(timestamp)
+    }
+
+    fn is_searcher(&self, tree: &TransactionTree) -> bool {
+        self.searchers.contains(&tree.from) ||
+            tree.to.is_some_and(|to| self.searchers.contains(&to))
+    }
+
+    fn price(&self, tree: &TransactionTree, swap: &Swap) -> Option<CexDex> {
+        let timestamp = *self.timestamps.get(&tree.block)?;
+        let (token_in, token_out) = (swap.token_in?, swap.token_out?);
+
+        let amount_in = self.whole_amount(&token_in, swap.amount_in?)?;
+        let amount_out = self.whole_amount(&token_out, swap.amount_out?)?;
+        if amount_in == 0.0 {
+            return None
+        }
+
+        let cex_price = self.prices.price_at(token_in, token_out, timestamp, self.max_price_age)?;
+        let dex_price = amount_out / amount_in;
+        if cex_price == 0.0 || ((dex_price - cex_price) / cex_price).abs() < self.min_divergence {
+            return None
+        }
+
+        Some(CexDex {
+            block: tree.block,
+            timestamp,
+            transaction: tree.hash,
+            searcher: tree.from,
+            contract: tree.to,
+            swap: swap.clone(),
+            dex_price,
+            cex_price,
+            edge: amount_out - amount_in * cex_price,
+        })
+    }
+
+    fn whole_amount(&self, token: &H160, amount: U256) -> Option<f64> {
+        let decimals = self.tokens.get(token)?.decimals;
+        Some(u256_to_f64(amount) / 10f64.powi(decimals as i32))
+    }
+}
+
+impl Inspector for CexDexInspector {
+    type Result = CexDex;
+
+    fn inspect(&self, trees: &[TransactionTree]) -> Vec<CexDex> {
+        trees
+            .iter()
+            .filter(|tree| self.is_searcher(tree))
+            .filter_map(|tree| match tree.swaps()[..] {
+                [swap] => self.price(tree, swap),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn parse_price_line(line: &str) -> Option<(u64, H160, H160, f64)> {
+    let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+    let [timestamp, base, quote, price] = fields[..] else { return None };
+
+    Some((
+        timestamp.parse().ok()?,
+        parse_address(base)?,
+        parse_address(quote)?,
+        price.parse().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        action::ActionType,
+        test_utils::{swap, tree},
+        tokens::TokenInfo,
+    };
+
+    const WETH: H160 = H160([1; 20]);
+    const USDC: H160 = H160([2; 20]);
+    const SEARCHER: H160 = H160([3; 20]);
+    const POOL: H160 = H160([4; 20]);
+
+    #[test]
+    fn parses_price_lines() {
+        let line = format!("1690000000, {WETH:?},{USDC:?}, 1850.5");
+        assert_eq!(parse_price_line(&line), Some((1_690_000_000, WETH, USDC, 1850.5)));
+
+        assert_eq!(parse_price_line("timestamp,base,quote,price"), None);
+        assert_eq!(parse_price_line(&format!("1690000000,{WETH:?},{USDC:?}")), None);
+        assert_eq!(parse_price_line(&format!("1690000000,0x01,{USDC:?},1850.5")), None);
+    }
+
+    #[test]
+    fn prices_at_the_last_fresh_timestamp() {
+        let mut prices = CexPrices::new();
+        prices.insert(WETH, USDC, 120, 1900.0);
+        prices.insert(WETH, USDC, 100, 1800.0);
+
+        assert_eq!(prices.price_at(WETH, USDC, 99, 60), None);
+        assert_eq!(prices.price_at(WETH, USDC, 100, 60), Some(1800.0));
+        assert_eq!(prices.price_at(WETH, USDC, 119, 60), Some(1800.0));
+        assert_eq!(prices.price_at(WETH, USDC, 150, 60), Some(1900.0));
+        assert_eq!(prices.price_at(WETH, USDC, 181, 60), None);
+    }
+
+    #[test]
+    fn inverts_the_reverse_pair() {
+        let mut prices = CexPrices::new();
+        prices.insert(WETH, USDC, 100, 2000.0);
+        prices.insert(USDC, H160::zero(), 100, 0.0);
+
+        assert_eq!(prices.price_at(USDC, WETH, 100, 60), Some(1.0 / 2000.0));
+        assert_eq!(prices.price_at(H160::zero(), USDC, 100, 60), None);
+    }
+
+    #[test]
+    fn loads_csv_files() {
+        let path = std::env::temp_dir().join(format!("cex-prices-{}.csv", std::process::id()));
+        let rows = [
+            "timestamp,base,quote,price".to_string(),
+            "# comment".to_string(),
+            String::new(),
+            format!("100,{WETH:?},{USDC:?},1800"),
+            format!("90,{WETH:?},{USDC:?},1750"),
+        ];
+        std::fs::write(&path, rows.join("\n")).unwrap();
+        let prices = CexPrices::from_csv(&path);
+
+        std::fs::write(&path, format!("100,{WETH:?},{USDC:?},1800\nnot a price\n")).unwrap();
+        let invalid = CexPrices::from_csv(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let prices = prices.unwrap();
+        assert_eq!(prices.price_at(WETH, USDC, 95, 60), Some(1750.0));
+        assert_eq!(prices.price_at(WETH, USDC, 100, 60), Some(1800.0));
+        assert!(invalid.is_err());
+    }
+
+    fn inspector() -> CexDexInspector {
+        let mut prices = CexPrices::new();
+        prices.insert(WETH, USDC, 100, 1800.0);
+
+        let mut tokens = TokenMetadata::new();
+        for (address, decimals) in [(WETH, 18), (USDC, 6)] {
+            tokens.insert(TokenInfo {
+                address,
+                decimals,
+                symbol: String::new(),
+                name: String::new(),
+            });
+        }
+
+        let mut inspector =
+            CexDexInspector::new(Arc::new(prices), Arc::new(tokens), HashSet::from([SEARCHER]));
+        inspector.timestamps.insert(1, 110);
+        inspector
+    }
+
+    fn sell_weth(amount_out: u64) -> ActionType {
+        ActionType::Swap(swap(POOL, WETH, USDC, 1_000_000_000_000_000_000, amount_out))
+    }
+
+    #[test]
+    fn prices_searcher_swaps_against_the_exchange() {
+        let trees = [
+            tree(0, SEARCHER, POOL, vec![sell_weth(1_900_000_000)]),
+            tree(1, H160([5; 20]), POOL, vec![sell_weth(1_900_000_000)]),
+            tree(2, SEARCHER, POOL, vec![sell_weth(1_900_000_000), sell_weth(1_900_000_000)]),
+        ];
+
+        let trades = inspector().inspect(&trees);
+
+        assert_eq!(trades.len(), 1);
+        let trade = &trades[0];
+        assert_eq!(trade.transaction, trees[0].hash);
+        assert_eq!(trade.timestamp, 110);
+        assert_eq!(trade.cex_price, 1800.0);
+        assert_eq!(trade.dex_price, 1900.0);
+        assert_eq!(trade.edge, 100.0);
+    }
+
+    #[test]
+    fn skips_swaps_within_the_minimum_divergence() {
+        let trees = [tree(0, SEARCHER, POOL, vec![sell_weth(1_850_000_000)])];
+
+        assert_eq!(inspector().inspect(&trees).len(), 1);
+        assert!(inspector().with_min_divergence(0.05).inspect(&trees).is_empty());
+        assert!(inspector().with_max_price_age(5).inspect(&trees).is_empty());
+    }
+}
@@ -0,0 +1,319 @@
+use super::Inspector;
+use crate::{
+    action::{ActionType, Burn, Collect, Mint, Protocol, Swap},
+    parser::to_h160,
+    tree::TransactionTree,
+};
+use alloy_primitives::U256;
+use reth_primitives::{H160, H256};
+use std::collections::HashSet;
+
+/// Liquidity added to a pool right before other accounts swap on it and removed right after.
+#[derive(Debug, Clone)]
+pub struct JitLiquidity {
+    pub block: u64,
+    pub protocol: Protocol,
+    pub pool: H160,
+    /// The account that sent the mint.
+    pub searcher: H160,
+    /// Owner of the position, usually the searcher's contract.
+    pub owner: H160,
+    pub mint: H256,
+    /// The swaps on the pool in the transactions between the mint and the burn.
+    pub victims: Vec<(H256, Swap)>,
+    pub burn: H256,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    /// Token amounts paid for the position, in the pool's token order.
+    pub capital: [U256; 2],
+    /// Collected amounts in excess of the burned liquidity, in the pool's token order. `None`
+    /// if the burn or collect amounts are unknown.
+    pub fees: Option<[U256; 2]>,
+}
+
+/// Most victim transactions a just-in-time position is searched across.
+pub const MAX_JIT_VICTIMS: usize = 8;
+
+/// Finds just-in-time liquidity: a Uniswap V3 style mint immediately followed by transactions of
+/// other accounts swapping on the same pool, then, in the transaction right after the last of
+/// them, a burn of the same tick range by the position's owner. At most [`MAX_JIT_VICTIMS`]
+/// transactions may sit between the mint and the burn.
+#[derive(Debug, Clone, Default)]
+pub struct JitInspector;
+
+impl Inspector for JitInspector {
+    type Result = JitLiquidity;
+
+    fn inspect(&self, trees: &[TransactionTree]) -> Vec<JitLiquidity> {
+        let mut positions = vec![];
+        let mut burns = HashSet::new();
+
+        for (mint_position, mint_tree) in trees.iter().enumerate() {
+            for mint in mints(mint_tree) {
+                let Some((amount_0, amount_1)) = mint.amount_0.zip(mint.amount_1) else { continue };
+                let owner = to_h160(&mint.owner);
+
+                let mut victims = vec![];
+                let mut burn = None;
+                let end = trees.len().min(mint_position + MAX_JIT_VICTIMS + 2);
+                for (position, tree) in trees.iter().enumerate().take(end).skip(mint_position + 1) {
+                    if !victims.is_empty() {
+                        burn =
+                            tree.actions().into_iter().enumerate().find_map(|(index, action)| {
+                                let ActionType::Burn(burn) = &action.ty else { return None };
+                                (!burns.contains(&(position, index)) && closes(burn, mint, owner))
+                                    .then_some((position, index, burn))
+                            });
+                        if burn.is_some() {
+                            break
+                        }
+                    }
+
+                    // Victims swap on the pool in consecutive transactions of their own
+                    if tree.from == mint_tree.from {
+                        break
+                    }
+                    let swaps = tree
+                        .swaps()
+                        .into_iter()
+                        .filter(|swap| swap.pool == mint.pool)
+                        .map(|swap| (tree.hash, swap.clone()))
+                        .collect::<Vec<_>>();
+                    if swaps.is_empty() {
+                        break
+                    }
+                    victims.extend(swaps);
+                }
+                let Some((burn_position, burn_index, burn)) = burn else { continue };
+
+                burns.insert((burn_position, burn_index));
+                positions.push(JitLiquidity {
+                    block: mint_tree.block,
+                    protocol: mint.protocol,
+                    pool: mint.pool,
+                    searcher: mint_tree.from,
+                    owner,
+                    mint: mint_tree.hash,
+                    victims,
+                    burn: trees[burn_position].hash,
+                    tick_lower: mint.tick_lower,
+                    tick_upper: mint.tick_upper,
+                    liquidity: mint.liquidity,
+                    capital: [amount_0, amount_1],
+                    fees: fees(burn, &trees[burn_position]),
+                });
+            }
+        }
+
+        positions
+    }
+}
+
+fn mints(tree: &TransactionTree) -> Vec<&Mint> {
+    tree.actions()
+        .into_iter()
+        .filter_map(|action| match &action.ty {
+            ActionType::Mint(mint) => Some(mint),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether `burn` removes liquidity from the position `mint` added to.
+fn closes(burn: &Burn, mint: &Mint, owner: H160) -> bool {
+    burn.pool == mint.pool &&
+        burn.owner == owner &&
+        burn.tick_lower == mint.tick_lower &&
+        burn.tick_upper == mint.tick_upper &&
+        burn.liquidity > 0
+}
+
+/// The fees collected along with the burned liquidity, i.e. what the position's collects in the
+/// burn's transaction withdrew beyond the burned amounts.
+fn fees(burn: &Burn, tree: &TransactionTree) -> Option<[U256; 2]> {
+    let (burned_0, burned_1) = burn.amount_0.zip(burn.amount_1)?;
+
+    let collects = tree
+        .actions()
+        .into_iter()
+        .filter_map(|action| match &action.ty {
+            ActionType::Collect(collect) if collects_from(collect, burn) => Some(collect),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if collects.is_empty() {
+        return None
+    }
+
+    let collected_0 = collects.iter().map(|collect| collect.amount_0).sum::<U256>();
+    let collected_1 = collects.iter().map(|collect| collect.amount_1).sum::<U256>();
+
+    Some([collected_0.saturating_sub(burned_0), collected_1.saturating_sub(burned_1)])
+}
+
+fn collects_from(collect: &Collect, burn: &Burn) -> bool {
+    collect.pool == burn.pool &&
+        collect.owner == burn.owner &&
+        collect.tick_lower == burn.tick_lower &&
+        collect.tick_upper == burn.tick_upper
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{swap, tree};
+    use alloy_primitives::Address;
+
+    const POOL: H160 = H160([1; 20]);
+    const SEARCHER: H160 = H160([2; 20]);
+    const OWNER: H160 = H160([3; 20]);
+    const TRADER: H160 = H160([4; 20]);
+    const ROUTER: H160 = H160([5; 20]);
+    const TOKEN_0: H160 = H160([6; 20]);
+    const TOKEN_1: H160 = H160([7; 20]);
+
+    fn mint() -> ActionType {
+        ActionType::Mint(Mint {
+            protocol: Protocol::UniswapV3,
+            pool: POOL,
+            owner: Address::from(OWNER.0),
+            tick_lower: -60,
+            tick_upper: 60,
+            liquidity: 1_000,
+            amount_0: Some(U256::from(100)),
+            amount_1: Some(U256::from(200)),
+        })
+    }
+
+    fn burn(owner: H160) -> ActionType {
+        ActionType::Burn(Burn {
+            protocol: Protocol::UniswapV3,
+            pool: POOL,
+            owner,
+            tick_lower: -60,
+            tick_upper: 60,
+            liquidity: 1_000,
+            amount_0: Some(U256::from(150)),
+            amount_1: Some(U256::from(120)),
+        })
+    }
+
+    fn collect() -> ActionType {
+        ActionType::Collect(Collect {
+            protocol: Protocol::UniswapV3,
+            pool: POOL,
+            owner: OWNER,
+            recipient: Address::from(OWNER.0),
+            tick_lower: -60,
+            tick_upper: 60,
+            amount_0: U256::from(151),
+            amount_1: U256::from(122),
+        })
+    }
+
+    fn victim(tx_index: usize, pool: H160) -> TransactionTree {
+        tree(tx_index, TRADER, ROUTER, vec![ActionType::Swap(swap(pool, TOKEN_0, TOKEN_1, 50, 80))])
+    }
+
+    #[test]
+    fn finds_liquidity_bracketing_swaps() {
+        let trees = [
+            tree(0, SEARCHER, OWNER, vec![mint()]),
+            victim(1, POOL),
+            tree(2, SEARCHER, OWNER, vec![burn(OWNER), collect()]),
+        ];
+
+        let positions = JitInspector.inspect(&trees);
+
+        assert_eq!(positions.len(), 1);
+        let position = &positions[0];
+        assert_eq!(position.pool, POOL);
+        assert_eq!(position.searcher, SEARCHER);
+        assert_eq!(position.owner, OWNER);
+        assert_eq!(position.mint, trees[0].hash);
+        assert_eq!(position.burn, trees[2].hash);
+        assert_eq!(position.victims.len(), 1);
+        assert_eq!(position.victims[0].0, trees[1].hash);
+        assert_eq!(position.liquidity, 1_000);
+        assert_eq!(position.capital, [U256::from(100), U256::from(200)]);
+        assert_eq!(position.fees, Some([U256::from(1), U256::from(2)]));
+    }
+
+    #[test]
+    fn leaves_fees_unknown_without_a_collect() {
+        let trees = [
+            tree(0, SEARCHER, OWNER, vec![mint()]),
+            victim(1, POOL),
+            tree(2, SEARCHER, OWNER, vec![burn(OWNER)]),
+        ];
+
+        let positions = JitInspector.inspect(&trees);
+
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].fees, None);
+    }
+
+    #[test]
+    fn ignores_positions_without_swaps_in_between() {
+        let trees = [
+            tree(0, SEARCHER, OWNER, vec![mint()]),
+            tree(1, SEARCHER, OWNER, vec![burn(OWNER), collect()]),
+        ];
+        assert!(JitInspector.inspect(&trees).is_empty());
+
+        let trees = [
+            tree(0, SEARCHER, OWNER, vec![mint()]),
+            victim(1, H160([8; 20])),
+            tree(2, SEARCHER, OWNER, vec![burn(OWNER), collect()]),
+        ];
+        assert!(JitInspector.inspect(&trees).is_empty());
+    }
+
+    #[test]
+    fn ignores_burns_of_other_positions() {
+        let trees = [
+            tree(0, SEARCHER, OWNER, vec![mint()]),
+            victim(1, POOL),
+            tree(2, SEARCHER, OWNER, vec![burn(H160([9; 20]))]),
+        ];
+
+        assert!(JitInspector.inspect(&trees).is_empty());
+    }
+
+    #[test]
+    fn collects_consecutive_victims() {
+        let trees = [
+            tree(0, SEARCHER, OWNER, vec![mint()]),
+            victim(1, POOL),
+            victim(2, POOL),
+            tree(3, SEARCHER, OWNER, vec![burn(OWNER), collect()]),
+        ];
+
+        let positions = JitInspector.inspect(&trees);
+
+        assert_eq!(positions.len(), 1);
+        let victims = positions[0].victims.iter().map(|(hash, _)| *hash).collect::<Vec<_>>();
+        assert_eq!(victims, vec![trees[1].hash, trees[2].hash]);
+    }
+
+    #[test]
+    fn ignores_victims_not_adjacent_to_the_mint_and_burn() {
+        let trees = [
+            tree(0, SEARCHER, OWNER, vec![mint()]),
+            victim(1, H160([8; 20])),
+            victim(2, POOL),
+            tree(3, SEARCHER, OWNER, vec![burn(OWNER), collect()]),
+        ];
+        assert!(JitInspector.inspect(&trees).is_empty());
+
+        let trees = [
+            tree(0, SEARCHER, OWNER, vec![mint()]),
+            victim(1, POOL),
+            victim(2, H160([8; 20])),
+            tree(3, SEARCHER, OWNER, vec![burn(OWNER), collect()]),
+        ];
+        assert!(JitInspector.inspect(&trees).is_empty());
+    }
+}
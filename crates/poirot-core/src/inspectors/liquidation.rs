@@ -0,0 +1,146 @@
+use super::{Inspector, PriceSource};
+use crate::{action::Liquidation, pricing::u256_to_f64, tree::TransactionTree};
+use alloy_primitives::U256;
+use reth_primitives::{H160, H256};
+
+/// A liquidation of a lending position, with the bonus captured by the liquidator.
+#[derive(Debug, Clone)]
+pub struct LiquidationReport {
+    pub block: u64,
+    pub transaction: H256,
+    /// The account that sent the transaction.
+    pub searcher: H160,
+    /// The contract called by the transaction.
+    pub contract: Option<H160>,
+    pub liquidation: Liquidation,
+    /// Value of the collateral seized minus value of the debt repaid, in the quote token of the
+    /// [`PriceSource`]. `None` if an amount or a price is unknown.
+    pub bonus: Option<f64>,
+}
+
+/// Reports every liquidation on a lending protocol and values the bonus the liquidator captured
+/// against the prices of a [`PriceSource`]. Compound V2 collateral is seized in cTokens, so the
+/// source needs cToken prices to value it, e.g. the ones
+/// [`OnChainPricer`](crate::pricing::OnChainPricer) derives from the market's exchange rate.
+#[derive(Debug, Clone)]
+pub struct LiquidationInspector<P> {
+    prices: P,
+}
+
+impl<P: PriceSource> LiquidationInspector<P> {
+    pub fn new(prices: P) -> Self {
+        Self { prices }
+    }
+
+    fn value(&self, token: Option<H160>, amount: Option<U256>) -> Option<f64> {
+        let price = self.prices.price(&token?)?;
+        Some(u256_to_f64(amount?) * price)
+    }
+
+    fn bonus(&self, liquidation: &Liquidation) -> Option<f64> {
+        let seized = self.value(liquidation.collateral_asset, liquidation.collateral_seized)?;
+        let repaid = self.value(liquidation.debt_asset, liquidation.debt_repaid)?;
+        Some(seized - repaid)
+    }
+}
+
+impl<P: PriceSource> Inspector for LiquidationInspector<P> {
+    type Result = LiquidationReport;
+
+    fn inspect(&self, trees: &[TransactionTree]) -> Vec<LiquidationReport> {
+        trees
+            .iter()
+            .flat_map(|tree| {
+                tree.liquidations().into_iter().map(|liquidation| LiquidationReport {
+                    block: tree.block,
+                    transaction: tree.hash,
+                    searcher: tree.from,
+                    contract: tree.to,
+                    liquidation: liquidation.clone(),
+                    bonus: self.bonus(liquidation),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        action::{ActionType, Protocol},
+        test_utils::tree,
+    };
+    use alloy_primitives::Address;
+    use std::collections::HashMap;
+
+    const POOL: H160 = H160([1; 20]);
+    const LIQUIDATOR: H160 = H160([2; 20]);
+    const CONTRACT: H160 = H160([3; 20]);
+    const DEBT: H160 = H160([4; 20]);
+    const COLLATERAL: H160 = H160([5; 20]);
+
+    fn liquidation(debt_repaid: Option<u64>, collateral_seized: Option<u64>) -> Liquidation {
+        Liquidation {
+            protocol: Protocol::AaveV2,
+            pool: POOL,
+            liquidator: CONTRACT,
+            borrower: Address::from([6; 20]),
+            debt_asset: Some(DEBT),
+            debt_repaid: debt_repaid.map(U256::from),
+            collateral_asset: Some(COLLATERAL),
+            collateral_seized: collateral_seized.map(U256::from),
+        }
+    }
+
+    fn inspector() -> LiquidationInspector<HashMap<H160, f64>> {
+        LiquidationInspector::new(HashMap::from([(DEBT, 1.0), (COLLATERAL, 2.0)]))
+    }
+
+    #[test]
+    fn values_liquidation_bonuses() {
+        let trees = [tree(
+            0,
+            LIQUIDATOR,
+            CONTRACT,
+            vec![
+                ActionType::Liquidation(liquidation(Some(100), Some(60))),
+                ActionType::BatchLiquidation(vec![
+                    liquidation(Some(10), Some(10)),
+                    liquidation(Some(50), Some(20)),
+                ]),
+            ],
+        )];
+
+        let reports = inspector().inspect(&trees);
+
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[0].transaction, trees[0].hash);
+        assert_eq!(reports[0].searcher, LIQUIDATOR);
+        assert_eq!(reports[0].contract, Some(CONTRACT));
+        assert_eq!(reports[0].bonus, Some(20.0));
+        assert_eq!(reports[1].bonus, Some(10.0));
+        assert_eq!(reports[2].bonus, Some(-10.0));
+    }
+
+    #[test]
+    fn leaves_bonuses_with_unknown_amounts_or_prices_unset() {
+        let mut unpriced = liquidation(Some(100), Some(60));
+        unpriced.collateral_asset = Some(H160([7; 20]));
+        let trees = [tree(
+            0,
+            LIQUIDATOR,
+            CONTRACT,
+            vec![
+                ActionType::Liquidation(liquidation(None, Some(60))),
+                ActionType::Liquidation(liquidation(Some(100), None)),
+                ActionType::Liquidation(unpriced),
+            ],
+        )];
+
+        let reports = inspector().inspect(&trees);
+
+        assert_eq!(reports.len(), 3);
+        assert!(reports.iter().all(|report| report.bonus.is_none()));
+    }
+}
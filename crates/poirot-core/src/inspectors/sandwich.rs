@@ -0,0 +1,290 @@
+use super::Inspector;
+use crate::{
+    action::{Protocol, Swap},
+    classifier::ContractClassifier,
+    tree::TransactionTree,
+};
+use alloy_primitives::{I256, U256};
+use reth_primitives::{H160, H256};
+use std::{collections::HashSet, sync::Arc};
+
+/// A searcher buying ahead of one or more victim swaps on a pool and selling right after them.
+#[derive(Debug, Clone)]
+pub struct Sandwich {
+    pub block: u64,
+    pub protocol: Protocol,
+    pub pool: H160,
+    /// The account that sent the front-run.
+    pub searcher: H160,
+    /// The searcher's contract called by the front-run, if any.
+    pub searcher_contract: Option<H160>,
+    pub frontrun: H256,
+    pub victims: Vec<VictimLoss>,
+    pub backrun: H256,
+    /// The token sold in the front-run and bought back in the back-run.
+    pub token: H160,
+    /// Back-run output minus front-run input, in `token`.
+    pub profit: I256,
+    /// The token bought in the front-run and sold in the back-run.
+    pub other_token: H160,
+    /// Front-run output not sold in the back-run, in `other_token`.
+    pub leftover: I256,
+}
+
+/// A swap sandwiched between the front-run and the back-run of a [`Sandwich`].
+#[derive(Debug, Clone)]
+pub struct VictimLoss {
+    pub transaction: H256,
+    pub sender: H160,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    /// Estimated output lost to the front-run, see [`SandwichInspector`].
+    pub loss: U256,
+}
+
+/// Finds sandwiches: a front-run swap and a back-run swap in the opposite direction on the same
+/// pool, sent by the same account or through the same searcher contract, around swaps of other
+/// accounts in the front-run's direction. Contracts known to the classifier are public protocol
+/// contracts and never identify a searcher.
+///
+/// A victim's loss is estimated as the output it would have received at the front-run's average
+/// price minus what it actually received. The front-run's average price is already worse than
+/// the pool price before it, so this underestimates the loss.
+#[derive(Debug, Clone)]
+pub struct SandwichInspector {
+    contracts: Arc<ContractClassifier>,
+}
+
+/// A swap whose tokens and amounts are known.
+struct Trade<'a> {
+    tree: &'a TransactionTree,
+    swap: &'a Swap,
+    token_in: H160,
+    token_out: H160,
+    amount_in: U256,
+    amount_out: U256,
+}
+
+impl SandwichInspector {
+    pub fn new(contracts: Arc<ContractClassifier>) -> Self {
+        Self { contracts }
+    }
+
+    /// The searcher contract called by the transaction, if it isn't a public protocol contract.
+    fn searcher_contract(&self, tree: &TransactionTree) -> Option<H160> {
+        tree.to.filter(|to| self.contracts.get(to).is_none())
+    }
+
+    fn same_searcher(&self, a: &TransactionTree, b: &TransactionTree) -> bool {
+        a.from == b.from || self.searcher_contract(a).is_some_and(|contract| Some(contract) == b.to)
+    }
+}
+
+impl Inspector for SandwichInspector {
+    type Result = Sandwich;
+
+    fn inspect(&self, trees: &[TransactionTree]) -> Vec<Sandwich> {
+        let trades = trees
+            .iter()
+            .map(|tree| {
+                tree.swaps().into_iter().filter_map(|swap| Trade::new(tree, swap)).collect()
+            })
+            .collect::<Vec<Vec<_>>>();
+
+        let mut sandwiches = vec![];
+        let mut backruns = HashSet::new();
+
+        for (front_position, front_trades) in trades.iter().enumerate() {
+            for front in front_trades {
+                // The closest later swap of the searcher undoing the front-run, leaving room for
+                // at least one victim
+                let back = (front_position + 2..trees.len()).find_map(|position| {
+                    if !self.same_searcher(front.tree, &trees[position]) {
+                        return None
+                    }
+
+                    trades[position].iter().enumerate().find_map(|(index, back)| {
+                        (!backruns.contains(&(position, index)) && back.reverses(front))
+                            .then_some((position, index, back))
+                    })
+                });
+                let Some((back_position, back_index, back)) = back else { continue };
+
+                let victims = trades[front_position + 1..back_position]
+                    .iter()
+                    .flatten()
+                    .filter(|victim| {
+                        victim.tree.from != front.tree.from &&
+                            victim.swap.pool == front.swap.pool &&
+                            victim.token_in == front.token_in &&
+                            victim.token_out == front.token_out
+                    })
+                    .map(|victim| victim.loss_to(front))
+                    .collect::<Vec<_>>();
+
+                if victims.is_empty() {
+                    continue
+                }
+
+                // Amounts past `I256::MAX` aren't real token amounts
+                let (Ok(front_in), Ok(front_out), Ok(back_in), Ok(back_out)) = (
+                    I256::try_from(front.amount_in),
+                    I256::try_from(front.amount_out),
+                    I256::try_from(back.amount_in),
+                    I256::try_from(back.amount_out),
+                ) else {
+                    continue
+                };
+
+                backruns.insert((back_position, back_index));
+                sandwiches.push(Sandwich {
+                    block: front.tree.block,
+                    protocol: front.swap.protocol,
+                    pool: front.swap.pool,
+                    searcher: front.tree.from,
+                    searcher_contract: self.searcher_contract(front.tree),
+                    frontrun: front.tree.hash,
+                    victims,
+                    backrun: back.tree.hash,
+                    token: front.token_in,
+                    profit: back_out - front_in,
+                    other_token: front.token_out,
+                    leftover: front_out - back_in,
+                });
+            }
+        }
+
+        sandwiches
+    }
+}
+
+impl<'a> Trade<'a> {
+    fn new(tree: &'a TransactionTree, swap: &'a Swap) -> Option<Self> {
+        Some(Self {
+            tree,
+            swap,
+            token_in: swap.token_in?,
+            token_out: swap.token_out?,
+            amount_in: swap.amount_in?,
+            amount_out: swap.amount_out?,
+        })
+    }
+
+    /// Whether this trade swaps back on the pool `other` traded on.
+    fn reverses(&self, other: &Trade<'_>) -> bool {
+        self.swap.pool == other.swap.pool &&
+            self.token_in == other.token_out &&
+            self.token_out == other.token_in
+    }
+
+    fn loss_to(&self, front: &Trade<'_>) -> VictimLoss {
+        let expected = if front.amount_in.is_zero() {
+            self.amount_out
+        } else {
+            self.amount_in.saturating_mul(front.amount_out) / front.amount_in
+        };
+
+        VictimLoss {
+            transaction: self.tree.hash,
+            sender: self.tree.from,
+            amount_in: self.amount_in,
+            amount_out: self.amount_out,
+            loss: expected.saturating_sub(self.amount_out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        action::ActionType,
+        classifier::ContractKind,
+        test_utils::{swap, tree},
+    };
+
+    const POOL: H160 = H160([1; 20]);
+    const OTHER_POOL: H160 = H160([2; 20]);
+    const TOKEN_A: H160 = H160([3; 20]);
+    const TOKEN_B: H160 = H160([4; 20]);
+    const SEARCHER: H160 = H160([5; 20]);
+    const SEARCHER_CONTRACT: H160 = H160([6; 20]);
+    const VICTIM: H160 = H160([7; 20]);
+    const ROUTER: H160 = H160([8; 20]);
+
+    fn inspector() -> SandwichInspector {
+        let mut contracts = ContractClassifier::new();
+        contracts.insert(ROUTER, ContractKind::Router(Protocol::UniswapV2));
+        SandwichInspector::new(Arc::new(contracts))
+    }
+
+    fn trade(pool: H160, token_in: H160, token_out: H160, amounts: (u64, u64)) -> ActionType {
+        ActionType::Swap(swap(pool, token_in, token_out, amounts.0, amounts.1))
+    }
+
+    fn front() -> TransactionTree {
+        tree(0, SEARCHER, SEARCHER_CONTRACT, vec![trade(POOL, TOKEN_A, TOKEN_B, (100, 50))])
+    }
+
+    fn victim(tx_index: usize, pool: H160) -> TransactionTree {
+        tree(tx_index, VICTIM, ROUTER, vec![trade(pool, TOKEN_A, TOKEN_B, (100, 40))])
+    }
+
+    fn back(tx_index: usize, from: H160, to: H160) -> TransactionTree {
+        tree(tx_index, from, to, vec![trade(POOL, TOKEN_B, TOKEN_A, (50, 120))])
+    }
+
+    #[test]
+    fn finds_sandwiches() {
+        let trees = [front(), victim(1, POOL), back(2, SEARCHER, SEARCHER_CONTRACT)];
+
+        let sandwiches = inspector().inspect(&trees);
+
+        assert_eq!(sandwiches.len(), 1);
+        let sandwich = &sandwiches[0];
+        assert_eq!(sandwich.pool, POOL);
+        assert_eq!(sandwich.searcher, SEARCHER);
+        assert_eq!(sandwich.searcher_contract, Some(SEARCHER_CONTRACT));
+        assert_eq!(sandwich.frontrun, trees[0].hash);
+        assert_eq!(sandwich.backrun, trees[2].hash);
+        assert_eq!(sandwich.token, TOKEN_A);
+        assert_eq!(sandwich.profit, I256::from_raw(U256::from(20)));
+        assert_eq!(sandwich.other_token, TOKEN_B);
+        assert_eq!(sandwich.leftover, I256::ZERO);
+
+        assert_eq!(sandwich.victims.len(), 1);
+        let victim = &sandwich.victims[0];
+        assert_eq!(victim.transaction, trees[1].hash);
+        assert_eq!(victim.sender, VICTIM);
+        assert_eq!(victim.loss, U256::from(10));
+    }
+
+    #[test]
+    fn matches_back_runs_sent_through_the_searcher_contract() {
+        let other_account = H160([9; 20]);
+        let trees = [front(), victim(1, POOL), back(2, other_account, SEARCHER_CONTRACT)];
+
+        assert_eq!(inspector().inspect(&trees).len(), 1);
+    }
+
+    #[test]
+    fn ignores_swaps_without_victims() {
+        let trees = [front(), back(1, SEARCHER, SEARCHER_CONTRACT)];
+        assert!(inspector().inspect(&trees).is_empty());
+
+        let trees = [front(), victim(1, OTHER_POOL), back(2, SEARCHER, SEARCHER_CONTRACT)];
+        assert!(inspector().inspect(&trees).is_empty());
+
+        let opposite = tree(1, VICTIM, ROUTER, vec![trade(POOL, TOKEN_B, TOKEN_A, (10, 20))]);
+        let trees = [front(), opposite, back(2, SEARCHER, SEARCHER_CONTRACT)];
+        assert!(inspector().inspect(&trees).is_empty());
+    }
+
+    #[test]
+    fn ignores_back_runs_of_other_accounts_through_public_contracts() {
+        let front = tree(0, SEARCHER, ROUTER, vec![trade(POOL, TOKEN_A, TOKEN_B, (100, 50))]);
+        let trees = [front, victim(1, POOL), back(2, H160([9; 20]), ROUTER)];
+
+        assert!(inspector().inspect(&trees).is_empty());
+    }
+}
@@ -13,8 +13,8 @@ use reth_db::{
     DatabaseError,
 };
 use reth_network_api::noop::NoopNetwork;
-use reth_primitives::MAINNET;
-use reth_provider::{providers::BlockchainProvider, ProviderFactory};
+use reth_primitives::{BlockId, Bytes, Header, H160, MAINNET};
+use reth_provider::{providers::BlockchainProvider, HeaderProvider, ProviderFactory};
 use reth_revm::Factory;
 use reth_rpc::{
     eth::{
@@ -23,6 +23,8 @@ use reth_rpc::{
     },
     DebugApi, EthApi, EthFilter, TraceApi, TracingCallGuard,
 };
+use reth_rpc_api::EthApiServer;
+use reth_rpc_types::CallRequest;
 use reth_tasks::TaskManager;
 use reth_transaction_pool::{EthTransactionValidator, GasCostOrdering, Pool, PooledTransaction};
 // Std
@@ -31,7 +33,20 @@ use tokio::runtime::Handle;
 
 pub mod abi;
 pub mod action;
+pub mod balances;
+pub mod classifier;
+pub mod events;
+pub mod inspectors;
 pub mod parser;
+pub mod pools;
+pub mod pricing;
+pub mod report;
+pub mod selectors;
+pub mod tokens;
+pub mod tree;
+
+#[cfg(test)]
+mod test_utils;
 
 pub type Provider = BlockchainProvider<
     Arc<Env<WriteMap>>,
@@ -44,6 +59,7 @@ pub type RethTxPool =
 pub type RethApi = EthApi<Provider, RethTxPool, NoopNetwork>;
 
 pub struct TracingClient {
+    pub provider: Provider,
     pub reth_api: EthApi<Provider, RethTxPool, NoopNetwork>,
     pub reth_trace: TraceApi<Provider, RethApi>,
     pub reth_filter: EthFilter<Provider, RethTxPool>,
@@ -120,9 +136,35 @@ impl TracingClient {
         );
 
         let reth_filter =
-            EthFilter::new(provider, tx_pool, state_cache, 1000, Box::new(task_executor));
+            EthFilter::new(provider.clone(), tx_pool, state_cache, 1000, Box::new(task_executor));
+
+        Self { provider, reth_api, reth_filter, reth_trace, reth_debug }
+    }
+
+    /// Executes a read-only call to `to` against the state at the end of `block`.
+    pub async fn eth_call(&self, to: H160, input: Vec<u8>, block: u64) -> eyre::Result<Bytes> {
+        let request = CallRequest { to: Some(to), data: Some(input.into()), ..Default::default() };
+
+        EthApiServer::call(&self.reth_api, request, Some(BlockId::from(block)), None)
+            .await
+            .map_err(|err| eyre::eyre!("eth_call to {to:?} at block {block} failed: {err}"))
+    }
+
+    /// Returns the header of `block`.
+    pub fn header(&self, block: u64) -> eyre::Result<Header> {
+        self.provider
+            .header_by_number(block)?
+            .ok_or_else(|| eyre::eyre!("Header of block {block} not found"))
+    }
+
+    /// Returns the timestamp of `block` from its header, in seconds since the epoch.
+    pub fn block_timestamp(&self, block: u64) -> eyre::Result<u64> {
+        Ok(self.header(block)?.timestamp)
+    }
 
-        Self { reth_api, reth_filter, reth_trace, reth_debug }
+    /// Returns the account `block`'s fees are paid to, usually its builder.
+    pub fn fee_recipient(&self, block: u64) -> eyre::Result<H160> {
+        Ok(self.header(block)?.beneficiary)
     }
 }
 
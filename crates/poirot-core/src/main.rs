@@ -1,24 +1,22 @@
-use ethers::prelude::k256::elliptic_curve::rand_core::block;
-use poirot_core::{parser::Parser, TracingClient};
-
-use poirot_core::action::ActionType;
-use std::{env, error::Error, path::Path};
-
-// reth types
-use reth_rpc_types::trace::parity::TraceType;
-use std::collections::HashSet;
-
-use reth_primitives::{BlockId, BlockNumberOrTag};
-use tracing::Subscriber;
-use tracing_subscriber::{
-    filter::Directive, prelude::*, registry::LookupSpan, EnvFilter, Layer, Registry,
+use poirot_core::{
+    abi::{parse_address, ContractAbiStorage},
+    classifier::ContractClassifier,
+    events::LogDecoder,
+    inspectors::{
+        ArbitrageInspector, Inspector, JitInspector, LiquidationInspector, SandwichInspector,
+    },
+    parser::Parser,
+    pools::PoolRegistry,
+    pricing::OnChainPricer,
+    report::{BlockMev, BlockReport},
+    TracingClient,
 };
-// reth types
-use reth_primitives::{BlockNumHash, H256};
-use reth_rpc_types::trace::geth::GethDebugTracingOptions;
-// alloy
-use alloy_json_abi::*;
-use std::str::FromStr;
+use reth_primitives::{BlockId, BlockNumberOrTag};
+use std::{env, error::Error, path::Path, sync::Arc};
+use tracing_subscriber::EnvFilter;
+
+/// The quote token of the report's values.
+const WETH: &str = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
 
 fn main() {
     let _ = tracing_subscriber::fmt()
@@ -29,29 +27,22 @@ fn main() {
     // Create the runtime
     let runtime = tokio_runtime().expect("Failed to create runtime");
 
-    let current_dir = env::current_dir().expect("Failed to get current directory");
-    let abi_dir = current_dir.parent().expect("Failed to get parent directory").join("abi");
+    let abi_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src").join("abi");
 
-    /*match load_all_jsonabis("abi") {
-        Ok(abis) => {
-            for abi in abis {
-                println!("Successfully loaded ABI");
-            }
-        }
-        Err(e) => eprintln!("Failed to load ABIs: {}", e)
-    } */
+    // Logs of contracts without a `sol!` interface are decoded through their ABIs
+    let abis = ContractAbiStorage::load_dir(&abi_dir).unwrap_or_else(|e| {
+        eprintln!("Failed to load ABIs: {}", e);
+        ContractAbiStorage::new()
+    });
 
     // Use the runtime to execute the async function
-    match runtime.block_on(run(runtime.handle().clone())) {
-        Ok(()) => println!("Success!"),
-        Err(e) => {
-            eprintln!("Error: {:?}", e);
-
-            let mut source: Option<&dyn Error> = e.source();
-            while let Some(err) = source {
-                eprintln!("Caused by: {:?}", err);
-                source = err.source();
-            }
+    if let Err(e) = runtime.block_on(run(runtime.handle().clone(), &abis)) {
+        eprintln!("Error: {:?}", e);
+
+        let mut source: Option<&dyn Error> = e.source();
+        while let Some(err) = source {
+            eprintln!("Caused by: {:?}", err);
+            source = err.source();
         }
     }
 }
@@ -64,7 +55,10 @@ pub fn tokio_runtime() -> Result<tokio::runtime::Runtime, std::io::Error> {
         .build()
 }
 
-async fn run(handle: tokio::runtime::Handle) -> Result<(), Box<dyn Error>> {
+async fn run(
+    handle: tokio::runtime::Handle,
+    abis: &ContractAbiStorage,
+) -> Result<(), Box<dyn Error>> {
     // Read environment variables
     let db_path = match env::var("DB_PATH") {
         Ok(path) => path,
@@ -76,63 +70,65 @@ async fn run(handle: tokio::runtime::Handle) -> Result<(), Box<dyn Error>> {
     // Initialize TracingClient
     let tracer = TracingClient::new(db_path, handle);
 
-    // Test
-    test(&tracer).await?;
-
-    /*
-
-    let parity_trace =
-        tracer.reth_trace.trace_block(BlockId::Number(BlockNumberOrTag::Latest)).await?;
-
-    let parser = Parser::new(parity_trace.unwrap());
-
-    for i in parser.parse() {
-        match i.ty {
-            // ActionType::Transfer(_) => println!("{i:#?}"),
-            ActionType::Swap(_) => println!("{i:#?}"),
-            _ => continue,
-        }
+    let parity_trace = tracer
+        .reth_trace
+        .trace_block(BlockId::Number(BlockNumberOrTag::Latest))
+        .await?
+        .unwrap_or_default();
+    let block = parity_trace
+        .first()
+        .and_then(|trace| trace.block_number)
+        .ok_or("The latest block has no traces")?;
+
+    let mut contracts = ContractClassifier::mainnet();
+    contracts.resolve_v3_pools(&tracer, &parity_trace, block).await;
+
+    let contracts = Arc::new(contracts);
+    let pools = PoolRegistry::mainnet();
+    let mut pricer = OnChainPricer::new(&pools, parse_address(WETH).unwrap());
+
+    let logs = LogDecoder::new(abis).decode_block(&tracer, block)?;
+    let parser = Parser::new(parity_trace, contracts.clone(), Arc::new(pools))
+        .with_fee_recipient(tracer.fee_recipient(block)?)
+        .with_logs(logs);
+    let trees = parser.parse();
+
+    // Liquidation bonuses are valued as the liquidations are inspected
+    let liquidations = trees.iter().flat_map(|tree| tree.liquidations());
+    let liquidation_tokens = liquidations
+        .flat_map(|liquidation| [liquidation.debt_asset, liquidation.collateral_asset])
+        .flatten()
+        .collect::<Vec<_>>();
+    for token in liquidation_tokens {
+        pricer.resolve(&tracer, token, block).await;
     }
 
-    // Print traces
-    */
+    let mev = BlockMev {
+        sandwiches: SandwichInspector::new(contracts).inspect(&trees),
+        arbitrages: ArbitrageInspector.inspect(&trees),
+        liquidations: LiquidationInspector::new(pricer.block_prices(block)).inspect(&trees),
+        jit: JitInspector.inspect(&trees),
+    };
 
-    Ok(())
-}
+    let profit_tokens = mev
+        .sandwiches
+        .iter()
+        .map(|sandwich| sandwich.token)
+        .chain(mev.arbitrages.iter().map(|arbitrage| arbitrage.token));
+    let fee_tokens = mev
+        .jit
+        .iter()
+        .flat_map(|position| &position.victims)
+        .flat_map(|(_, swap)| [swap.token_in, swap.token_out])
+        .flatten();
+    for token in profit_tokens.chain(fee_tokens).collect::<Vec<_>>() {
+        pricer.resolve(&tracer, token, block).await;
+    }
 
-async fn test(tracer: &TracingClient) -> Result<(), Box<dyn std::error::Error>> {
-    let tx_hash_str = "0x6f4c57c271b9054dbe31833d20138b15838e1482384c0cd6b1be44db54805bce";
-    let tx_hash = H256::from_str(tx_hash_str)?;
-
-    let trace_types: HashSet<TraceType> =
-        vec![TraceType::Trace, TraceType::VmTrace, TraceType::StateDiff].into_iter().collect();
-
-    let trace_results = tracer.reth_trace.replay_transaction(tx_hash, trace_types).await?;
-
-    // Print traces
-    trace_results
-        .trace
-        .as_ref()
-        .map(|trace| {
-            trace.iter().for_each(|t| println!("{:#?}", t));
-        })
-        .unwrap_or_else(|| println!("No regular trace found for transaction."));
-
-    trace_results
-        .vm_trace
-        .as_ref()
-        .map(|vm_trace| {
-            println!("{:#?}", vm_trace);
-        })
-        .unwrap_or_else(|| println!("No VM trace found for transaction."));
-
-    trace_results
-        .state_diff
-        .as_ref()
-        .map(|state_diff| {
-            println!("{:#?}", state_diff);
-        })
-        .unwrap_or_else(|| println!("No state diff found for transaction."));
+    // Print the block report
+    let report =
+        BlockReport::build(&tracer, block, &trees, &mev, &pricer.block_prices(block)).await?;
+    println!("{}", report.to_json()?);
 
     Ok(())
 }
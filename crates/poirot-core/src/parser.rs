@@ -1,6 +1,43 @@
-use crate::action::{Action, ActionType, Deposit, PoolCreation, Swap, Transfer, Withdrawal};
+use crate::{
+    action::{
+        Action, ActionType, CoinbaseTransfer, Deposit, PoolCreation, ProposerPayment, Protocol,
+        Swap, Transfer, Withdrawal,
+    },
+    classifier::{ContractClassifier, ContractKind},
+    events::DecodedLog,
+    pools::PoolRegistry,
+    tree::{is_sub_call, ActionNode, EthTransfer, TransactionTree},
+};
+use alloy_primitives::Address;
+use reth_primitives::H160;
+use std::{iter::Peekable, sync::Arc};
 
-use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
+mod aave;
+mod balancer;
+mod bancor;
+mod compound;
+mod curve;
+mod dodo;
+mod kyber;
+mod mooniswap;
+mod uniswap_v2;
+mod uniswap_v3;
+
+pub use aave::IAaveLendingPool;
+pub use balancer::{IBalancerVault, IFlashLoanRecipient};
+pub use bancor::IBancorNetwork;
+pub use compound::{ICErc20, ICEther, IComet};
+pub use curve::{ICurveCryptoSwap, ICurvePool2, ICurvePool3, ICurvePool4, ICurveStableSwap};
+pub use dodo::{IDodoV1Pair, IDodoV2Pool, IDodoV3Pool, IDodoV3Vault};
+pub use kyber::IKyberNetworkProxy;
+pub use mooniswap::IMooniswap;
+pub use uniswap_v2::{
+    IUniswapV2Callee, IUniswapV2Factory, IUniswapV2Pair, IUniswapV2Router02, UNISWAP_V2_FEE,
+};
+
+use reth_rpc_types::trace::parity::{
+    Action as RethAction, CallType, LocalizedTransactionTrace, TraceOutput,
+};
 
 use alloy_sol_types::{sol, SolCall, SolInterface};
 
@@ -9,6 +46,8 @@ sol! {
     interface IERC20 {
         function transfer(address to, uint256 amount) external returns (bool);
         function transferFrom(address from, address to, uint256 amount) external returns (bool);
+
+        event Transfer(address indexed from, address indexed to, uint256 value);
     }
 }
 
@@ -25,6 +64,13 @@ sol! {
         function setOwner(address _owner) external;
         function enableFeeAmount(uint24 fee, int24 tickSpacing) external;
 
+        event PoolCreated(
+            address indexed token0,
+            address indexed token1,
+            uint24 indexed fee,
+            int24 tickSpacing,
+            address pool
+        );
     }
 }
 
@@ -79,6 +125,9 @@ sol! {
     interface WETH9 {
         function deposit() public payable;
         function withdraw(uint wad) public;
+
+        event Deposit(address indexed dst, uint wad);
+        event Withdrawal(address indexed src, uint wad);
     }
 }
 
@@ -86,6 +135,7 @@ sol! {
     #[derive(Debug, PartialEq)]
     interface IUniswapV3Pool {
         function swap(address recipient, bool zeroForOne, int256 amountSpecified, uint160 sqrtPriceLimitX96, bytes data) external override returns (int256, int256);
+        function factory() external view returns (address);
         function mint(
             address recipient,
             int24 tickLower,
@@ -111,64 +161,244 @@ sol! {
             uint256 amount1,
             bytes calldata data
         ) external;
+
+        event Swap(
+            address indexed sender,
+            address indexed recipient,
+            int256 amount0,
+            int256 amount1,
+            uint160 sqrtPriceX96,
+            uint128 liquidity,
+            int24 tick
+        );
+        event Mint(
+            address sender,
+            address indexed owner,
+            int24 indexed tickLower,
+            int24 indexed tickUpper,
+            uint128 amount,
+            uint256 amount0,
+            uint256 amount1
+        );
+        event Burn(
+            address indexed owner,
+            int24 indexed tickLower,
+            int24 indexed tickUpper,
+            uint128 amount,
+            uint256 amount0,
+            uint256 amount1
+        );
     }
 }
 
 pub struct Parser {
     block_trace: Vec<LocalizedTransactionTrace>,
+    contracts: Arc<ContractClassifier>,
+    pools: Arc<PoolRegistry>,
+    /// The block's fee recipient, see [`Parser::with_fee_recipient`].
+    fee_recipient: Option<H160>,
+    /// Position of the block's last transaction.
+    last_transaction: Option<u64>,
+    /// The block's decoded logs, see [`Parser::with_logs`].
+    logs: Vec<DecodedLog>,
 }
-//TODO: Instead of directly going from trace to action we should have an intermediatary filter step
-//TODO: This step could be used to filter known contract interactions & directly match on the
-// appropriate decoder instead of naively looping thorugh all of them
 
 impl Parser {
-    pub fn new(block_trace: Vec<LocalizedTransactionTrace>) -> Self {
-        Self { block_trace }
+    pub fn new(
+        block_trace: Vec<LocalizedTransactionTrace>,
+        contracts: Arc<ContractClassifier>,
+        pools: Arc<PoolRegistry>,
+    ) -> Self {
+        let last_transaction =
+            block_trace.iter().filter_map(|trace| trace.transaction_position).max();
+
+        Self { block_trace, contracts, pools, fee_recipient: None, last_transaction, logs: vec![] }
     }
 
-    pub fn parse(&self) -> Vec<Action> {
-        let mut actions = vec![];
+    /// Sets the fee recipient of the block, see [`TracingClient::fee_recipient`], to classify
+    /// the ether paid to it and the builder's payment to the proposer.
+    ///
+    /// [`TracingClient::fee_recipient`]: crate::TracingClient::fee_recipient
+    pub fn with_fee_recipient(mut self, fee_recipient: H160) -> Self {
+        self.fee_recipient = Some(fee_recipient);
+        self
+    }
 
-        for i in self.block_trace.clone() {
-            let parsed = self.parse_trace(&i);
+    /// Sets the decoded logs of the block, see [`LogDecoder`], to complete actions whose amounts
+    /// are only emitted, like the collateral absorbed by a Compound V3 `absorb`.
+    ///
+    /// [`LogDecoder`]: crate::events::LogDecoder
+    pub fn with_logs(mut self, logs: Vec<DecodedLog>) -> Self {
+        self.logs = logs;
+        self
+    }
 
-            if parsed.is_some() {
-                actions.push(parsed.unwrap());
-            } else {
-                actions.push(Action {
-                    ty: ActionType::Unclassified(i.clone()),
-                    hash: i.clone().transaction_hash.unwrap(),
-                    block: i.clone().block_number.unwrap(),
-                });
+    /// Parses the block into one call tree per transaction.
+    pub fn parse(&self) -> Vec<TransactionTree> {
+        let mut trees = vec![];
+        // Block and uncle reward traces don't belong to a transaction
+        let mut traces =
+            self.block_trace.iter().filter(|trace| trace.transaction_hash.is_some()).peekable();
+
+        while let Some(first) = traces.peek() {
+            let tx_index = first.transaction_position.unwrap_or_default() as usize;
+            let (from, to) = match &first.trace.action {
+                RethAction::Call(call) => (call.from, Some(call.to)),
+                RethAction::Create(create) => (create.from, None),
+                _ => (H160::zero(), None),
+            };
+            let Some(root) = self.build_node(&mut traces) else { break };
+
+            let mut tree = TransactionTree {
+                hash: root.action.hash,
+                block: root.action.block,
+                tx_index,
+                from,
+                to,
+                root,
+            };
+            tree.fill_swaps();
+            tree.fill_liquidations();
+
+            trees.push(tree);
+        }
+
+        trees
+    }
+
+    /// Builds the node for the next trace and, recursively, its sub-calls. Relies on the traces
+    /// of a transaction being ordered depth first, as returned by `trace_block`.
+    fn build_node<'a, I>(&self, traces: &mut Peekable<I>) -> Option<ActionNode>
+    where
+        I: Iterator<Item = &'a LocalizedTransactionTrace>,
+    {
+        let curr = traces.next()?;
+        let mut action = self.classify(curr);
+        let trace_address = curr.trace.trace_address.clone();
+
+        let mut children = Vec::with_capacity(curr.trace.subtraces);
+        while let Some(next) = traces.peek() {
+            if !is_sub_call(&trace_address, &next.trace.trace_address) {
+                break
             }
+            children.extend(self.build_node(traces));
         }
 
-        actions
+        uniswap_v3::complete_from_callbacks(&mut action, &children);
+        compound::complete_from_seize(&mut action, &children);
+        compound::complete_from_absorb(&mut action, curr, &self.logs);
+
+        let mut node = ActionNode::new(action, trace_address, children);
+        node.value = eth_transfer(curr);
+        node.reverted = curr.trace.error.is_some();
+
+        Some(node)
+    }
+
+    /// Classifies a trace, falling back to [`ActionType::Unclassified`].
+    pub fn classify(&self, curr: &LocalizedTransactionTrace) -> Action {
+        self.parse_trace(curr)
+            .unwrap_or_else(|| Action::new(ActionType::Unclassified(curr.clone()), curr))
     }
 
-    //TODO: Note, because a transaction can be a swap -> transfer -> transfer we would have to
-    // avoid double counting the transfer & essentially create a higher TODO: level swap action
-    // that contains its subsequent transfers
-    /// Parse a single transaction trace.
+    /// Parse a single transaction trace, routing it to the decoders for the kind of contract it
+    /// calls. Pools missing from the classification table are looked up in the pool registry;
+    /// calls to any other unknown contract are only decoded if they are ERC20 transfers.
     pub fn parse_trace(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
-        self.parse_transfer(curr)
-            .or_else(|| self.parse_pool_creation(curr))
-            .or_else(|| self.parse_weth(curr))
-            .or_else(|| self.parse_swap(curr))
-    }
-    // TODO: So here we kind of have to create a type for each contract abi, and we can automate
-    // that by using the alloy json abi, so we can just decode them for any function, & then
-    // after that we sort for actions of interest, so even if we don't care about it for mev we
-    // still know wtf is going on so we can eaisly see what we are missing that could
-    // potentially be interesting because we have our db that is going to have all contract
-    // address so we want to do something where we are automating new integrations
-    // & can do something like this collect contract addr classified by (type, so like dex etc..) ->
-    // abi -> function -> action type -> mev filtering if of interest So you are basically
-    // filtering by contract address, then match to a pool of function selectors, then match to
-    // decode which has corresponding action type From this we then inspect for mev by filtering
-    // through all the actions of interest
-
-    pub fn parse_swap(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        if let Some(action) = self.parse_fee_recipient_payment(curr) {
+            return Some(action)
+        }
+
+        let kind = self
+            .contracts
+            .get(&call.to)
+            .or_else(|| self.pools.get(&call.to).map(|pool| ContractKind::Pool(pool.protocol)));
+        let Some(kind) = kind else { return self.parse_transfer(curr) };
+
+        match kind {
+            ContractKind::Erc20 => self.parse_transfer(curr),
+            ContractKind::Weth => self.parse_weth(curr).or_else(|| self.parse_transfer(curr)),
+            ContractKind::Factory(protocol @ (Protocol::UniswapV2 | Protocol::Sushiswap)) |
+            ContractKind::Factory(protocol @ (Protocol::UniswapV3 | Protocol::SushiswapV3)) => {
+                self.parse_pool_creation(curr, protocol)
+            }
+            ContractKind::Pool(protocol @ (Protocol::UniswapV3 | Protocol::SushiswapV3)) => self
+                .parse_swap(curr, protocol)
+                .or_else(|| self.parse_uniswap_v3_liquidity(curr, protocol)),
+            ContractKind::Pool(protocol @ (Protocol::UniswapV2 | Protocol::Sushiswap)) => {
+                self.parse_uniswap_v2_pair(curr, protocol)
+            }
+            ContractKind::Router(protocol @ (Protocol::UniswapV2 | Protocol::Sushiswap)) => {
+                self.parse_uniswap_v2_router(curr, protocol)
+            }
+            ContractKind::Pool(Protocol::Curve) => self.parse_curve(curr),
+            ContractKind::Vault(Protocol::Balancer) => self.parse_balancer_vault(curr),
+            ContractKind::Router(Protocol::Bancor) => self.parse_bancor(curr),
+            ContractKind::Router(Protocol::Kyber) => self.parse_kyber_proxy(curr),
+            // Kyber DMM pools share the UniswapV2 pair interface
+            ContractKind::Pool(Protocol::Kyber) => {
+                self.parse_uniswap_v2_pair(curr, Protocol::Kyber)
+            }
+            ContractKind::Pool(Protocol::Mooniswap) => self.parse_mooniswap(curr),
+            ContractKind::Pool(Protocol::Dodo) => self.parse_dodo_v1(curr),
+            ContractKind::Pool(Protocol::DodoV2) => self.parse_dodo_v2(curr),
+            ContractKind::Pool(Protocol::DodoV3) | ContractKind::Vault(Protocol::DodoV3) => {
+                self.parse_dodo_v3(curr)
+            }
+            ContractKind::Pool(protocol @ (Protocol::AaveV2 | Protocol::AaveV3)) => {
+                self.parse_aave_pool(curr, protocol)
+            }
+            ContractKind::Pool(Protocol::CompoundV2) => self.parse_compound_v2(curr),
+            ContractKind::Pool(Protocol::CompoundV3) => self.parse_compound_v3(curr),
+            _ => None,
+        }
+    }
+
+    /// Parses ether paid to the block's fee recipient: the builder's plain transfer to the
+    /// proposer in the block's last transaction, or any other call sending it value.
+    pub fn parse_fee_recipient_payment(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+        let fee_recipient = self.fee_recipient?;
+        if call.value.is_zero() ||
+            call.call_type == CallType::DelegateCall ||
+            curr.trace.error.is_some()
+        {
+            return None
+        }
+
+        let ty = if call.from == fee_recipient &&
+            call.input.is_empty() &&
+            curr.trace.trace_address.is_empty() &&
+            curr.transaction_position.is_some() &&
+            curr.transaction_position == self.last_transaction
+        {
+            ActionType::ProposerPayment(ProposerPayment {
+                builder: fee_recipient,
+                proposer: call.to,
+                amount: call.value,
+            })
+        } else if call.to == fee_recipient {
+            ActionType::CoinbaseTransfer(CoinbaseTransfer {
+                from: call.from,
+                fee_recipient,
+                amount: call.value,
+            })
+        } else {
+            return None
+        };
+
+        Some(Action::new(ty, curr))
+    }
+
+    /// Parses a swap on a Uniswap V3 pool or a fork of it. The protocol is the one of the factory
+    /// that deployed the pool, see [`ContractClassifier::resolve_v3_pools`].
+    pub fn parse_swap(
+        &self,
+        curr: &LocalizedTransactionTrace,
+        protocol: Protocol,
+    ) -> Option<Action> {
         match &curr.trace.action {
             RethAction::Call(call) => {
                 let mut decoded = match IUniswapV3Pool::swapCall::decode(&call.input.to_vec(), true)
@@ -177,17 +407,40 @@ impl Parser {
                     Err(_) => return None,
                 };
 
-                return Some(Action {
-                    ty: ActionType::Swap(Swap {
-                        recipient: decoded.recipient,
-                        direction: decoded.zeroForOne,
-                        amount_specified: decoded.amountSpecified,
-                        price_limit: decoded.sqrtPriceLimitX96,
-                        data: decoded.data,
-                    }),
-                    hash: curr.transaction_hash.unwrap(),
-                    block: curr.block_number.unwrap(),
-                })
+                let (index_in, index_out) = if decoded.zeroForOne { (0, 1) } else { (1, 0) };
+
+                // The pool returns its balance deltas, positive for the token it received.
+                // Without them only the specified side is known, negative meaning exact output.
+                let (amount_in, amount_out) = match call_output(curr)
+                    .and_then(|output| IUniswapV3Pool::swapCall::decode_returns(output, true).ok())
+                {
+                    Some(deltas) => {
+                        let (delta_in, delta_out) = if decoded.zeroForOne {
+                            (deltas._0, deltas._1)
+                        } else {
+                            (deltas._1, deltas._0)
+                        };
+                        (Some(delta_in.unsigned_abs()), Some(delta_out.unsigned_abs()))
+                    }
+                    None if decoded.amountSpecified.is_negative() => {
+                        (None, Some(decoded.amountSpecified.unsigned_abs()))
+                    }
+                    None => (Some(decoded.amountSpecified.unsigned_abs()), None),
+                };
+
+                return Some(Action::new(
+                    ActionType::Swap(Swap::new(
+                        protocol,
+                        call.to,
+                        call.from,
+                        decoded.recipient,
+                        self.pools.token(&call.to, index_in, false),
+                        self.pools.token(&call.to, index_out, false),
+                        amount_in,
+                        amount_out,
+                    )),
+                    curr,
+                ))
             }
             _ => None,
         }
@@ -203,21 +456,20 @@ impl Parser {
 
                 match decoded {
                     WETH9::WETH9Calls::deposit(deposit_call) => {
-                        return Some(Action {
-                            ty: ActionType::WethDeposit(Deposit::new(call.from, call.value)),
-                            hash: curr.transaction_hash.unwrap(),
-                            block: curr.block_number.unwrap(),
-                        })
+                        return Some(Action::new(
+                            ActionType::WethDeposit(Deposit::new(call.from, call.value, call.to)),
+                            curr,
+                        ))
                     }
                     WETH9::WETH9Calls::withdraw(withdraw_call) => {
-                        return Some(Action {
-                            ty: ActionType::WethWithdraw(Withdrawal::new(
+                        return Some(Action::new(
+                            ActionType::WethWithdraw(Withdrawal::new(
                                 call.from,
                                 withdraw_call.wad,
+                                call.to,
                             )),
-                            hash: curr.transaction_hash.unwrap(),
-                            block: curr.block_number.unwrap(),
-                        })
+                            curr,
+                        ))
                     }
                     _ => return None,
                 }
@@ -226,9 +478,12 @@ impl Parser {
         }
     }
 
+    /// Parses an ERC20 `transfer` or `transferFrom` on any contract. Delegatecalls are skipped: a
+    /// proxied token forwards the same input to its implementation, which would report the
+    /// transfer twice.
     pub fn parse_transfer(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
         match &curr.trace.action {
-            RethAction::Call(call) => {
+            RethAction::Call(call) if call.call_type != CallType::DelegateCall => {
                 let mut decoded = match IERC20::IERC20Calls::decode(&call.input.to_vec(), true) {
                     Ok(decoded) => decoded,
                     Err(_) => return None,
@@ -236,26 +491,26 @@ impl Parser {
 
                 match decoded {
                     IERC20::IERC20Calls::transfer(transfer_call) => {
-                        return Some(Action {
-                            ty: ActionType::Transfer(Transfer::new(
+                        return Some(Action::new(
+                            ActionType::Transfer(Transfer::new(
+                                Address::from(call.from.0),
                                 transfer_call.to,
                                 transfer_call.amount.into(),
                                 call.to,
                             )),
-                            hash: curr.transaction_hash.unwrap(),
-                            block: curr.block_number.unwrap(),
-                        })
+                            curr,
+                        ))
                     }
                     IERC20::IERC20Calls::transferFrom(transfer_from_call) => {
-                        return Some(Action {
-                            ty: ActionType::Transfer(Transfer::new(
+                        return Some(Action::new(
+                            ActionType::Transfer(Transfer::new(
+                                transfer_from_call.from,
                                 transfer_from_call.to,
                                 transfer_from_call.amount.into(),
                                 call.to,
                             )),
-                            hash: curr.transaction_hash.unwrap(),
-                            block: curr.block_number.unwrap(),
-                        })
+                            curr,
+                        ))
                     }
                     _ => return None,
                 }
@@ -264,26 +519,309 @@ impl Parser {
         }
     }
 
-    pub fn parse_pool_creation(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
-        match &curr.trace.action {
-            RethAction::Call(call) => {
-                let mut decoded =
-                    match IUniswapV3Factory::createPoolCall::decode(&call.input.to_vec(), true) {
-                        Ok(decoded) => decoded,
-                        Err(_) => return None,
-                    };
-
-                return Some(Action {
-                    ty: ActionType::PoolCreation(PoolCreation::new(
-                        decoded.tokenA,
-                        decoded.tokenB,
-                        decoded.fee,
-                    )),
-                    hash: curr.transaction_hash.unwrap(),
-                    block: curr.block_number.unwrap(),
-                })
-            }
-            _ => None,
+    /// Parses a Uniswap V3 style `createPool` or a UniswapV2 style `createPair` on a factory of
+    /// `protocol`.
+    pub fn parse_pool_creation(
+        &self,
+        curr: &LocalizedTransactionTrace,
+        protocol: Protocol,
+    ) -> Option<Action> {
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let output = call_output(curr);
+
+        let (token_a, token_b, fee, pool) =
+            if let Ok(decoded) = IUniswapV3Factory::createPoolCall::decode(&call.input, true) {
+                let pool = output.and_then(|output| {
+                    IUniswapV3Factory::createPoolCall::decode_returns(output, true).ok()
+                });
+                (decoded.tokenA, decoded.tokenB, decoded.fee, pool.map(|created| created._0))
+            } else {
+                let decoded = IUniswapV2Factory::createPairCall::decode(&call.input, true).ok()?;
+                let pool = output.and_then(|output| {
+                    IUniswapV2Factory::createPairCall::decode_returns(output, true).ok()
+                });
+                (decoded.tokenA, decoded.tokenB, UNISWAP_V2_FEE, pool.map(|created| created.pair))
+            };
+
+        let (token_0, token_1) =
+            if token_a < token_b { (token_a, token_b) } else { (token_b, token_a) };
+
+        Some(Action::new(
+            ActionType::PoolCreation(PoolCreation::new(
+                protocol,
+                call.to,
+                pool.as_ref().map(to_h160),
+                token_0,
+                token_1,
+                fee,
+            )),
+            curr,
+        ))
+    }
+}
+
+/// The ether moved by a trace, if it succeeded. Delegate calls run with the value of their
+/// caller and move nothing.
+fn eth_transfer(curr: &LocalizedTransactionTrace) -> Option<EthTransfer> {
+    if curr.trace.error.is_some() {
+        return None
+    }
+
+    let transfer = match &curr.trace.action {
+        RethAction::Call(call) if call.call_type != CallType::DelegateCall => {
+            EthTransfer { from: call.from, to: call.to, amount: call.value }
+        }
+        RethAction::Create(create) => {
+            let Some(TraceOutput::Create(output)) = &curr.trace.result else { return None };
+            EthTransfer { from: create.from, to: output.address, amount: create.value }
         }
+        RethAction::Selfdestruct(selfdestruct) => EthTransfer {
+            from: selfdestruct.address,
+            to: selfdestruct.refund_address,
+            amount: selfdestruct.balance,
+        },
+        _ => return None,
+    };
+
+    (!transfer.amount.is_zero()).then_some(transfer)
+}
+
+/// Returns the return data of a successful call trace.
+pub fn call_output(curr: &LocalizedTransactionTrace) -> Option<&[u8]> {
+    if curr.trace.error.is_some() {
+        return None
+    }
+
+    match &curr.trace.result {
+        Some(TraceOutput::Call(output)) => Some(&output.output[..]),
+        _ => None,
+    }
+}
+
+/// Converts an address decoded by `sol!` into the address type used by traces.
+pub(crate) fn to_h160(address: &Address) -> H160 {
+    H160::from_slice(&address[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        self, address_word, at, call_trace, returning, reverted, transfer_input, word,
+    };
+    use alloy_primitives::{Address, U256};
+    use reth_primitives::H160;
+
+    const POOL: H160 = H160([1; 20]);
+    const TRADER: H160 = H160([2; 20]);
+    const ROUTER: H160 = H160([3; 20]);
+    const TOKEN_A: H160 = H160([4; 20]);
+    const TOKEN_B: H160 = H160([5; 20]);
+    const OTHER: H160 = H160([6; 20]);
+
+    fn parser(block_trace: Vec<LocalizedTransactionTrace>) -> Parser {
+        test_utils::parser(
+            block_trace,
+            &[
+                (POOL, ContractKind::Pool(Protocol::UniswapV3)),
+                (TOKEN_A, ContractKind::Erc20),
+                (TOKEN_B, ContractKind::Erc20),
+            ],
+            &[(POOL, test_utils::pool(Protocol::UniswapV3, &[TOKEN_A, TOKEN_B]))],
+        )
+    }
+
+    /// A V3 `swap` of 1000 token0 from `TRADER`, returning the pool's balance deltas.
+    fn v3_swap(output: Vec<u8>) -> LocalizedTransactionTrace {
+        let input = [
+            &IUniswapV3Pool::swapCall::SELECTOR[..],
+            &address_word(TRADER)[..],
+            &word(1)[..],
+            &word(1000)[..],
+            &word(0)[..],
+            &word(0xa0)[..],
+            &word(0)[..],
+        ]
+        .concat();
+
+        returning(call_trace(TRADER, POOL, input), output)
+    }
+
+    fn as_swap(action: Option<Action>) -> Swap {
+        match action.map(|action| action.ty) {
+            Some(ActionType::Swap(swap)) => swap,
+            ty => panic!("expected a swap, got {ty:?}"),
+        }
+    }
+
+    #[test]
+    fn records_v3_swap_deltas() {
+        let parser = parser(vec![]);
+        let swap = as_swap(
+            parser.parse_swap(&v3_swap([word(1000), word(-990)].concat()), Protocol::UniswapV3),
+        );
+
+        assert_eq!(swap.recipient, Address::from(TRADER.0));
+        assert_eq!(
+            (swap.pool, swap.token_in, swap.token_out),
+            (POOL, Some(TOKEN_A), Some(TOKEN_B))
+        );
+        assert_eq!(swap.sender, TRADER);
+        assert_eq!(
+            (swap.amount_in, swap.amount_out),
+            (Some(U256::from(1000)), Some(U256::from(990)))
+        );
+    }
+
+    #[test]
+    fn keeps_the_specified_amount_of_reverted_swaps() {
+        let parser = parser(vec![]);
+
+        let swap = as_swap(parser.parse_swap(&reverted(v3_swap(vec![])), Protocol::UniswapV3));
+        assert_eq!((swap.amount_in, swap.amount_out), (Some(U256::from(1000)), None));
+
+        let swap = as_swap(parser.parse_swap(&v3_swap(word(1000).to_vec()), Protocol::UniswapV3));
+        assert_eq!((swap.amount_in, swap.amount_out), (Some(U256::from(1000)), None));
+    }
+
+    /// A router swap through a V3 pool, which pays out, calls back into the router for its
+    /// input and lets it make an unrelated call, followed by a plain transfer.
+    fn block_trace() -> Vec<LocalizedTransactionTrace> {
+        let callback = [
+            &IUniswapV3SwapCallback::uniswapV3SwapCallbackCall::SELECTOR[..],
+            &word(1000)[..],
+            &word(-990)[..],
+            &word(0x60)[..],
+            &word(0)[..],
+        ]
+        .concat();
+
+        vec![
+            at(call_trace(TRADER, ROUTER, vec![0x12, 0x34, 0x56, 0x78]), 0, vec![], 1),
+            at(v3_swap([word(1000), word(-990)].concat()), 0, vec![0], 2),
+            at(call_trace(POOL, TOKEN_B, transfer_input(TRADER, 990)), 0, vec![0, 0], 0),
+            at(call_trace(POOL, ROUTER, callback), 0, vec![0, 1], 2),
+            at(call_trace(ROUTER, TOKEN_A, transfer_input(POOL, 1000)), 0, vec![0, 1, 0], 0),
+            at(call_trace(ROUTER, OTHER, vec![0xde, 0xad, 0xbe, 0xef]), 0, vec![0, 1, 1], 0),
+            at(call_trace(TRADER, TOKEN_A, transfer_input(OTHER, 5)), 1, vec![], 0),
+        ]
+    }
+
+    #[test]
+    fn builds_a_tree_per_transaction() {
+        let trees = parser(block_trace()).parse();
+
+        assert_eq!(trees.len(), 2);
+        assert_eq!(trees[0].tx_index, 0);
+        assert_eq!(trees[1].tx_index, 1);
+        assert!(matches!(trees[1].root.action.ty, ActionType::Transfer(_)));
+        assert!(trees[1].root.children.is_empty());
+    }
+
+    #[test]
+    fn absorbs_swap_transfers_and_callbacks() {
+        let trees = parser(block_trace()).parse();
+        let router = &trees[0].root;
+        assert!(matches!(router.action.ty, ActionType::Unclassified(_)));
+        assert_eq!(router.children.len(), 1);
+
+        let swap = &router.children[0];
+        assert!(matches!(swap.action.ty, ActionType::Swap(_)));
+        assert_eq!(swap.absorbed.len(), 2);
+        assert!(matches!(swap.absorbed[0].action.ty, ActionType::Transfer(_)));
+        assert_eq!(swap.absorbed[1].trace_address, [0, 1]);
+        assert_eq!(swap.absorbed[1].absorbed.len(), 1);
+
+        // The unrelated call made from the callback is still an action of the transaction
+        assert_eq!(swap.children.len(), 1);
+        assert_eq!(swap.children[0].trace_address, [0, 1, 1]);
+        assert_eq!(trees[0].actions().len(), 3);
+    }
+
+    #[test]
+    fn routes_traces_by_contract_kind() {
+        let parser = parser(vec![]);
+
+        let transfer = call_trace(TRADER, TOKEN_A, transfer_input(OTHER, 5));
+        assert!(matches!(parser.classify(&transfer).ty, ActionType::Transfer(_)));
+
+        // The same calldata sent to an unknown contract, or a swap sent to a token
+        let unknown = call_trace(TRADER, OTHER, transfer_input(OTHER, 5));
+        assert!(matches!(parser.classify(&unknown).ty, ActionType::Unclassified(_)));
+        let mut swap = v3_swap(vec![]);
+        if let RethAction::Call(call) = &mut swap.trace.action {
+            call.to = TOKEN_A;
+        }
+        assert!(matches!(parser.classify(&swap).ty, ActionType::Unclassified(_)));
+    }
+
+    #[test]
+    fn tags_v3_fork_swaps_with_their_protocol() {
+        let fork = H160([7; 20]);
+        let parser =
+            test_utils::parser(vec![], &[(fork, ContractKind::Pool(Protocol::SushiswapV3))], &[]);
+        let mut swap = v3_swap(vec![]);
+        if let RethAction::Call(call) = &mut swap.trace.action {
+            call.to = fork;
+        }
+
+        let ActionType::Swap(swap) = parser.classify(&swap).ty else { panic!("expected a swap") };
+        assert_eq!(swap.protocol, Protocol::SushiswapV3);
+        assert_eq!(swap.pool, fork);
+    }
+
+    const FEE_RECIPIENT: H160 = H160([7; 20]);
+    const PROPOSER: H160 = H160([8; 20]);
+
+    fn paying(mut trace: LocalizedTransactionTrace, value: u64) -> LocalizedTransactionTrace {
+        if let RethAction::Call(call) = &mut trace.trace.action {
+            call.value = U256::from(value);
+        }
+        trace
+    }
+
+    /// A direct payment to the fee recipient, one made by a contract the searcher calls and the
+    /// builder paying the proposer in the last transaction.
+    fn payments_trace() -> Vec<LocalizedTransactionTrace> {
+        vec![
+            at(paying(call_trace(TRADER, FEE_RECIPIENT, vec![]), 5), 0, vec![], 0),
+            at(call_trace(TRADER, ROUTER, vec![0x12, 0x34, 0x56, 0x78]), 1, vec![], 1),
+            at(paying(call_trace(ROUTER, FEE_RECIPIENT, vec![]), 7), 1, vec![0], 0),
+            at(paying(call_trace(FEE_RECIPIENT, PROPOSER, vec![]), 100), 2, vec![], 0),
+        ]
+    }
+
+    #[test]
+    fn classifies_payments_to_the_fee_recipient() {
+        let trees = parser(payments_trace()).with_fee_recipient(FEE_RECIPIENT).parse();
+        assert_eq!(trees.len(), 3);
+
+        let transfers = trees[0].coinbase_transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!((transfers[0].from, transfers[0].amount), (TRADER, U256::from(5)));
+        assert!(matches!(trees[0].root.action.ty, ActionType::CoinbaseTransfer(_)));
+
+        let transfers = trees[1].coinbase_transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!((transfers[0].from, transfers[0].amount), (ROUTER, U256::from(7)));
+        assert_eq!(transfers[0].fee_recipient, FEE_RECIPIENT);
+
+        let ActionType::ProposerPayment(payment) = &trees[2].root.action.ty else {
+            panic!("expected the proposer payment, got {:?}", trees[2].root.action.ty)
+        };
+        assert_eq!((payment.builder, payment.proposer), (FEE_RECIPIENT, PROPOSER));
+        assert_eq!(payment.amount, U256::from(100));
+    }
+
+    #[test]
+    fn needs_the_fee_recipient_to_classify_payments() {
+        let trees = parser(payments_trace()).parse();
+        assert!(trees.iter().all(|tree| tree.coinbase_transfers().is_empty()));
+        assert!(matches!(trees[2].root.action.ty, ActionType::Unclassified(_)));
+
+        let parser = parser(vec![]).with_fee_recipient(FEE_RECIPIENT);
+        let failed = reverted(paying(call_trace(TRADER, FEE_RECIPIENT, vec![]), 5));
+        assert!(parser.parse_fee_recipient_payment(&failed).is_none());
     }
 }
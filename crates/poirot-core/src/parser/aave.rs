@@ -0,0 +1,46 @@
+use super::{to_h160, Parser};
+use crate::action::{Action, ActionType, Liquidation, Protocol};
+use alloy_sol_types::{sol, SolCall};
+use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
+
+sol! {
+    /// The liquidation entrypoint shared by the Aave V2 `LendingPool` and the Aave V3 `Pool`.
+    #[derive(Debug, PartialEq)]
+    interface IAaveLendingPool {
+        function liquidationCall(
+            address collateralAsset,
+            address debtAsset,
+            address user,
+            uint256 debtToCover,
+            bool receiveAToken
+        ) external;
+    }
+}
+
+impl Parser {
+    /// Parses a `liquidationCall` on an Aave V2 or V3 pool. `debtToCover` is only an upper bound
+    /// (commonly `type(uint256).max`), so the amounts are left to the transfers.
+    pub fn parse_aave_pool(
+        &self,
+        curr: &LocalizedTransactionTrace,
+        protocol: Protocol,
+    ) -> Option<Action> {
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let decoded = IAaveLendingPool::liquidationCallCall::decode(&call.input, true).ok()?;
+
+        Some(Action::new(
+            ActionType::Liquidation(Liquidation {
+                protocol,
+                pool: call.to,
+                liquidator: call.from,
+                borrower: decoded.user,
+                debt_asset: Some(to_h160(&decoded.debtAsset)),
+                debt_repaid: None,
+                collateral_asset: Some(to_h160(&decoded.collateralAsset)),
+                collateral_seized: None,
+            }),
+            curr,
+        ))
+    }
+}
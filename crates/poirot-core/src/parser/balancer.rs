@@ -0,0 +1,424 @@
+use super::{call_output, to_h160, Parser};
+use crate::action::{Action, ActionType, AddLiquidity, FlashLoan, Protocol, RemoveLiquidity, Swap};
+use alloy_primitives::{Address, I256, U256};
+use alloy_sol_types::{sol, SolCall, SolInterface};
+use reth_primitives::H160;
+use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
+use std::collections::HashMap;
+
+sol! {
+    struct SingleSwap {
+        bytes32 poolId;
+        uint8 kind;
+        address assetIn;
+        address assetOut;
+        uint256 amount;
+        bytes userData;
+    }
+
+    struct BatchSwapStep {
+        bytes32 poolId;
+        uint256 assetInIndex;
+        uint256 assetOutIndex;
+        uint256 amount;
+        bytes userData;
+    }
+
+    struct FundManagement {
+        address sender;
+        bool fromInternalBalance;
+        address recipient;
+        bool toInternalBalance;
+    }
+
+    struct JoinPoolRequest {
+        address[] assets;
+        uint256[] maxAmountsIn;
+        bytes userData;
+        bool fromInternalBalance;
+    }
+
+    struct ExitPoolRequest {
+        address[] assets;
+        uint256[] minAmountsOut;
+        bytes userData;
+        bool toInternalBalance;
+    }
+
+    #[derive(Debug, PartialEq)]
+    interface IBalancerVault {
+        function swap(
+            SingleSwap singleSwap,
+            FundManagement funds,
+            uint256 limit,
+            uint256 deadline
+        ) external payable returns (uint256 amountCalculated);
+        function batchSwap(
+            uint8 kind,
+            BatchSwapStep[] swaps,
+            address[] assets,
+            FundManagement funds,
+            int256[] limits,
+            uint256 deadline
+        ) external payable returns (int256[] assetDeltas);
+        function joinPool(
+            bytes32 poolId,
+            address sender,
+            address recipient,
+            JoinPoolRequest request
+        ) external payable;
+        function exitPool(
+            bytes32 poolId,
+            address sender,
+            address recipient,
+            ExitPoolRequest request
+        ) external;
+        function flashLoan(
+            address recipient,
+            address[] tokens,
+            uint256[] amounts,
+            bytes userData
+        ) external;
+    }
+}
+
+sol! {
+    interface IFlashLoanRecipient {
+        function receiveFlashLoan(
+            address[] tokens,
+            uint256[] amounts,
+            uint256[] feeAmounts,
+            bytes userData
+        ) external;
+    }
+}
+
+/// `SwapKind.GIVEN_IN`; anything else is `GIVEN_OUT`.
+const GIVEN_IN: u8 = 0;
+
+impl Parser {
+    /// Parses a call to the Balancer V2 Vault. A `batchSwap` is expanded into one [`Swap`] per
+    /// pool hop.
+    pub fn parse_balancer_vault(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let decoded = IBalancerVault::IBalancerVaultCalls::decode(&call.input, true).ok()?;
+
+        let ty = match decoded {
+            IBalancerVault::IBalancerVaultCalls::swap(swap_call) => {
+                let single = swap_call.singleSwap;
+                let mut swap = balancer_swap(
+                    &single.poolId[..],
+                    call.from,
+                    single.kind,
+                    single.assetIn,
+                    single.assetOut,
+                    single.amount,
+                    swap_call.funds.recipient,
+                );
+
+                // The vault returns the amount of the side that wasn't specified
+                if let Some(returned) = call_output(curr)
+                    .and_then(|output| IBalancerVault::swapCall::decode_returns(output, true).ok())
+                {
+                    if single.kind == GIVEN_IN {
+                        swap.amount_out = Some(returned.amountCalculated);
+                    } else {
+                        swap.amount_in = Some(returned.amountCalculated);
+                    }
+                }
+
+                ActionType::Swap(swap)
+            }
+            IBalancerVault::IBalancerVaultCalls::batchSwap(batch_call) => {
+                // A step with an asset index out of `assets` is skipped on its own
+                let mut hops = batch_call
+                    .swaps
+                    .into_iter()
+                    .filter_map(|step| {
+                        let asset_in = usize::try_from(step.assetInIndex).ok()?;
+                        let asset_out = usize::try_from(step.assetOutIndex).ok()?;
+
+                        let swap = balancer_swap(
+                            &step.poolId[..],
+                            call.from,
+                            batch_call.kind,
+                            *batch_call.assets.get(asset_in)?,
+                            *batch_call.assets.get(asset_out)?,
+                            step.amount,
+                            batch_call.funds.recipient,
+                        );
+                        Some((asset_in, asset_out, swap))
+                    })
+                    .collect::<Vec<_>>();
+
+                if let Some(returned) = call_output(curr).and_then(|output| {
+                    IBalancerVault::batchSwapCall::decode_returns(output, true).ok()
+                }) {
+                    fill_from_asset_deltas(&mut hops, &returned.assetDeltas);
+                }
+
+                ActionType::BatchSwap(hops.into_iter().map(|(_, _, swap)| swap).collect())
+            }
+            // Join and exit requests only carry the caller's limits; the settled amounts are
+            // emitted in `PoolBalanceChanged`
+            IBalancerVault::IBalancerVaultCalls::joinPool(join_call) => {
+                ActionType::AddLiquidity(AddLiquidity {
+                    protocol: Protocol::Balancer,
+                    pool: pool_address(&join_call.poolId[..]),
+                    recipient: join_call.recipient,
+                    amounts: join_call.request.maxAmountsIn,
+                    limits: true,
+                    liquidity: None,
+                })
+            }
+            IBalancerVault::IBalancerVaultCalls::exitPool(exit_call) => {
+                ActionType::RemoveLiquidity(RemoveLiquidity {
+                    protocol: Protocol::Balancer,
+                    pool: pool_address(&exit_call.poolId[..]),
+                    recipient: exit_call.recipient,
+                    amounts: exit_call.request.minAmountsOut,
+                    limits: true,
+                    liquidity: None,
+                })
+            }
+            IBalancerVault::IBalancerVaultCalls::flashLoan(flash_call) => {
+                ActionType::FlashLoan(FlashLoan {
+                    protocol: Protocol::Balancer,
+                    lender: call.to,
+                    recipient: flash_call.recipient,
+                    tokens: flash_call.tokens.iter().map(to_h160).collect(),
+                    amounts: flash_call.amounts,
+                    fees: vec![],
+                })
+            }
+        };
+
+        Some(Action::new(ty, curr))
+    }
+}
+
+/// Builds a [`Swap`] for one Balancer pool hop, knowing only the amount of the side `kind`
+/// specifies.
+fn balancer_swap(
+    pool_id: &[u8],
+    sender: H160,
+    kind: u8,
+    asset_in: Address,
+    asset_out: Address,
+    amount: U256,
+    recipient: Address,
+) -> Swap {
+    // A zero amount means the hop uses the output of the previous one
+    let amount = (!amount.is_zero()).then_some(amount);
+    let (amount_in, amount_out) = if kind == GIVEN_IN { (amount, None) } else { (None, amount) };
+
+    Swap::new(
+        Protocol::Balancer,
+        pool_address(pool_id),
+        sender,
+        recipient,
+        Some(to_h160(&asset_in)),
+        Some(to_h160(&asset_out)),
+        amount_in,
+        amount_out,
+    )
+}
+
+/// Fills in the amounts of the hops of a `batchSwap` from the vault's net `assetDeltas`, positive
+/// for what it received and negative for what it sent. A delta is only one hop's amount if no
+/// other hop trades the asset, so the amounts of the tokens passed between hops stay unknown.
+fn fill_from_asset_deltas(hops: &mut [(usize, usize, Swap)], deltas: &[I256]) {
+    let mut trades = HashMap::<usize, usize>::new();
+    for (asset_in, asset_out, _) in hops.iter() {
+        *trades.entry(*asset_in).or_default() += 1;
+        *trades.entry(*asset_out).or_default() += 1;
+    }
+    let delta = |asset: usize| deltas.get(asset).copied().filter(|_| trades[&asset] == 1);
+
+    for (asset_in, asset_out, swap) in hops.iter_mut() {
+        if let Some(paid) = delta(*asset_in).filter(|delta| delta.is_positive()) {
+            swap.amount_in = Some(paid.into_raw());
+        }
+        if let Some(received) = delta(*asset_out).filter(|delta| delta.is_negative()) {
+            swap.amount_out = Some(received.unsigned_abs());
+        }
+    }
+}
+
+/// Balancer pool IDs start with the address of the pool contract.
+fn pool_address(pool_id: &[u8]) -> H160 {
+    H160::from_slice(&pool_id[..20])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        classifier::ContractKind,
+        test_utils::{call_trace, parser, returning, word},
+    };
+
+    const VAULT: H160 = H160([1; 20]);
+    const POOL: H160 = H160([2; 20]);
+    const OTHER_POOL: H160 = H160([3; 20]);
+    const TRADER: H160 = H160([4; 20]);
+    const TOKEN_A: H160 = H160([5; 20]);
+    const TOKEN_B: H160 = H160([6; 20]);
+    const TOKEN_C: H160 = H160([7; 20]);
+
+    fn parse(trace: LocalizedTransactionTrace) -> ActionType {
+        let parser = parser(vec![], &[(VAULT, ContractKind::Vault(Protocol::Balancer))], &[]);
+        parser.classify(&trace).ty
+    }
+
+    fn address(address: H160) -> Address {
+        Address::from(address.0)
+    }
+
+    /// A pool ID: the pool's address followed by its specialization and nonce.
+    fn pool_id(pool: H160) -> [u8; 32] {
+        let mut id = [0; 32];
+        id[..20].copy_from_slice(&pool.0);
+        id[31] = 1;
+        id
+    }
+
+    fn funds() -> FundManagement {
+        FundManagement {
+            sender: address(TRADER),
+            fromInternalBalance: false,
+            recipient: address(TRADER),
+            toInternalBalance: false,
+        }
+    }
+
+    fn step(pool: H160, asset_in: u64, asset_out: u64, amount: u64) -> BatchSwapStep {
+        BatchSwapStep {
+            poolId: pool_id(pool).into(),
+            assetInIndex: U256::from(asset_in),
+            assetOutIndex: U256::from(asset_out),
+            amount: U256::from(amount),
+            userData: vec![],
+        }
+    }
+
+    fn batch_swap(swaps: Vec<BatchSwapStep>) -> Vec<u8> {
+        IBalancerVault::batchSwapCall {
+            kind: GIVEN_IN,
+            swaps,
+            assets: vec![address(TOKEN_A), address(TOKEN_B), address(TOKEN_C)],
+            funds: funds(),
+            limits: vec![],
+            deadline: U256::MAX,
+        }
+        .encode()
+    }
+
+    #[test]
+    fn parses_single_swaps() {
+        let input = IBalancerVault::swapCall {
+            singleSwap: SingleSwap {
+                poolId: pool_id(POOL).into(),
+                kind: GIVEN_IN,
+                assetIn: address(TOKEN_A),
+                assetOut: address(TOKEN_B),
+                amount: U256::from(100),
+                userData: vec![],
+            },
+            funds: funds(),
+            limit: U256::from(90),
+            deadline: U256::MAX,
+        }
+        .encode();
+        let trace = returning(call_trace(TRADER, VAULT, input), word(95).to_vec());
+
+        let ActionType::Swap(swap) = parse(trace) else { panic!("expected a swap") };
+        assert_eq!(
+            (swap.pool, swap.token_in, swap.token_out),
+            (POOL, Some(TOKEN_A), Some(TOKEN_B))
+        );
+        assert_eq!(swap.sender, TRADER);
+        assert_eq!(
+            (swap.amount_in, swap.amount_out),
+            (Some(U256::from(100)), Some(U256::from(95)))
+        );
+    }
+
+    #[test]
+    fn expands_batch_swaps_into_hops() {
+        let input = batch_swap(vec![step(POOL, 0, 1, 100), step(OTHER_POOL, 1, 2, 0)]);
+
+        let ActionType::BatchSwap(swaps) = parse(call_trace(TRADER, VAULT, input)) else {
+            panic!("expected a batch swap")
+        };
+        assert_eq!(swaps.len(), 2);
+        assert_eq!((swaps[0].pool, swaps[0].token_in), (POOL, Some(TOKEN_A)));
+        assert_eq!((swaps[1].pool, swaps[1].token_out), (OTHER_POOL, Some(TOKEN_C)));
+        assert_eq!((swaps[0].amount_in, swaps[0].amount_out), (Some(U256::from(100)), None));
+        // The second hop spends whatever the first one bought
+        assert_eq!((swaps[1].amount_in, swaps[1].amount_out), (None, None));
+    }
+
+    #[test]
+    fn skips_batch_steps_with_unknown_assets() {
+        let input = batch_swap(vec![step(POOL, 0, 1, 100), step(OTHER_POOL, 1, 3, 0)]);
+
+        let ActionType::BatchSwap(swaps) = parse(call_trace(TRADER, VAULT, input)) else {
+            panic!("expected a batch swap")
+        };
+        assert_eq!(swaps.len(), 1);
+        assert_eq!(swaps[0].pool, POOL);
+    }
+
+    #[test]
+    fn fills_batch_hops_from_asset_deltas() {
+        let input = batch_swap(vec![step(POOL, 0, 1, 100), step(OTHER_POOL, 1, 2, 0)]);
+        let deltas = [word(0x20), word(3), word(100), word(0), word(-95)].concat();
+        let trace = returning(call_trace(TRADER, VAULT, input), deltas);
+
+        let ActionType::BatchSwap(swaps) = parse(trace) else { panic!("expected a batch swap") };
+        assert_eq!((swaps[0].amount_in, swaps[0].amount_out), (Some(U256::from(100)), None));
+        assert_eq!((swaps[1].amount_in, swaps[1].amount_out), (None, Some(U256::from(95))));
+    }
+
+    #[test]
+    fn marks_join_amounts_as_limits() {
+        let input = IBalancerVault::joinPoolCall {
+            poolId: pool_id(POOL).into(),
+            sender: address(TRADER),
+            recipient: address(TRADER),
+            request: JoinPoolRequest {
+                assets: vec![address(TOKEN_A), address(TOKEN_B)],
+                maxAmountsIn: vec![U256::from(10), U256::from(20)],
+                userData: vec![],
+                fromInternalBalance: false,
+            },
+        }
+        .encode();
+
+        let ActionType::AddLiquidity(deposit) = parse(call_trace(TRADER, VAULT, input)) else {
+            panic!("expected a deposit")
+        };
+        assert_eq!(deposit.pool, POOL);
+        assert_eq!(deposit.amounts, [U256::from(10), U256::from(20)]);
+        assert!(deposit.limits);
+    }
+
+    #[test]
+    fn parses_flash_loans() {
+        let input = IBalancerVault::flashLoanCall {
+            recipient: address(TRADER),
+            tokens: vec![address(TOKEN_A)],
+            amounts: vec![U256::from(1000)],
+            userData: vec![],
+        }
+        .encode();
+
+        let ActionType::FlashLoan(loan) = parse(call_trace(TRADER, VAULT, input)) else {
+            panic!("expected a flash loan")
+        };
+        assert_eq!((loan.lender, loan.tokens), (VAULT, vec![TOKEN_A]));
+        assert_eq!(loan.amounts, [U256::from(1000)]);
+    }
+}
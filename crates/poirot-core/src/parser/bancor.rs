@@ -0,0 +1,188 @@
+use super::{call_output, to_h160, Parser};
+use crate::action::{Action, ActionType, AddLiquidity, Protocol, RemoveLiquidity, Swap};
+use alloy_primitives::Address;
+use alloy_sol_types::{sol, SolCall, SolInterface};
+use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface IBancorNetwork {
+        function tradeBySourceAmount(
+            address sourceToken,
+            address targetToken,
+            uint256 sourceAmount,
+            uint256 minReturnAmount,
+            uint256 deadline,
+            address beneficiary
+        ) external payable returns (uint256);
+        function tradeByTargetAmount(
+            address sourceToken,
+            address targetToken,
+            uint256 targetAmount,
+            uint256 maxSourceAmount,
+            uint256 deadline,
+            address beneficiary
+        ) external payable returns (uint256);
+        function deposit(address pool, uint256 tokenAmount) external payable returns (uint256);
+        function depositFor(
+            address provider,
+            address pool,
+            uint256 tokenAmount
+        ) external payable returns (uint256);
+        function withdraw(uint256 id) external returns (uint256);
+    }
+}
+
+impl Parser {
+    /// Parses a call to the Bancor V3 network. Bancor V3 pools are identified by their token
+    /// and all trades settle in the network, so the network is reported as the pool.
+    pub fn parse_bancor(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
+        use IBancorNetwork::IBancorNetworkCalls as Calls;
+
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let decoded = Calls::decode(&call.input, true).ok()?;
+        let output = call_output(curr);
+
+        // A zero beneficiary means the trader receives the output
+        let beneficiary = |beneficiary: Address| {
+            if beneficiary == Address::ZERO {
+                Address::from(call.from.0)
+            } else {
+                beneficiary
+            }
+        };
+
+        let ty = match decoded {
+            Calls::tradeBySourceAmount(trade_call) => {
+                let received = output.and_then(|output| {
+                    IBancorNetwork::tradeBySourceAmountCall::decode_returns(output, true)
+                        .ok()
+                        .map(|traded| traded._0)
+                });
+                ActionType::Swap(Swap::new(
+                    Protocol::Bancor,
+                    call.to,
+                    call.from,
+                    beneficiary(trade_call.beneficiary),
+                    Some(to_h160(&trade_call.sourceToken)),
+                    Some(to_h160(&trade_call.targetToken)),
+                    Some(trade_call.sourceAmount),
+                    received,
+                ))
+            }
+            Calls::tradeByTargetAmount(trade_call) => {
+                let paid = output.and_then(|output| {
+                    IBancorNetwork::tradeByTargetAmountCall::decode_returns(output, true)
+                        .ok()
+                        .map(|traded| traded._0)
+                });
+                ActionType::Swap(Swap::new(
+                    Protocol::Bancor,
+                    call.to,
+                    call.from,
+                    beneficiary(trade_call.beneficiary),
+                    Some(to_h160(&trade_call.sourceToken)),
+                    Some(to_h160(&trade_call.targetToken)),
+                    paid,
+                    Some(trade_call.targetAmount),
+                ))
+            }
+            Calls::deposit(deposit_call) => ActionType::AddLiquidity(AddLiquidity {
+                protocol: Protocol::Bancor,
+                pool: to_h160(&deposit_call.pool),
+                recipient: Address::from(call.from.0),
+                amounts: vec![deposit_call.tokenAmount],
+                limits: false,
+                liquidity: output.and_then(|output| {
+                    IBancorNetwork::depositCall::decode_returns(output, true)
+                        .ok()
+                        .map(|deposited| deposited._0)
+                }),
+            }),
+            Calls::depositFor(deposit_call) => ActionType::AddLiquidity(AddLiquidity {
+                protocol: Protocol::Bancor,
+                pool: to_h160(&deposit_call.pool),
+                recipient: deposit_call.provider,
+                amounts: vec![deposit_call.tokenAmount],
+                limits: false,
+                liquidity: output.and_then(|output| {
+                    IBancorNetwork::depositForCall::decode_returns(output, true)
+                        .ok()
+                        .map(|deposited| deposited._0)
+                }),
+            }),
+            // Withdrawals complete a previously initiated request, so only the amount paid out
+            // is known
+            Calls::withdraw(_) => ActionType::RemoveLiquidity(RemoveLiquidity {
+                protocol: Protocol::Bancor,
+                pool: call.to,
+                recipient: Address::from(call.from.0),
+                amounts: output
+                    .and_then(|output| {
+                        IBancorNetwork::withdrawCall::decode_returns(output, true).ok()
+                    })
+                    .map(|withdrawn| vec![withdrawn._0])
+                    .unwrap_or_default(),
+                limits: false,
+                liquidity: None,
+            }),
+        };
+
+        Some(Action::new(ty, curr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        classifier::ContractKind,
+        test_utils::{call_trace, parser, returning, word},
+    };
+    use alloy_primitives::U256;
+    use reth_primitives::H160;
+
+    const NETWORK: H160 = H160([1; 20]);
+    const TRADER: H160 = H160([2; 20]);
+    const TOKEN_A: H160 = H160([3; 20]);
+    const TOKEN_B: H160 = H160([4; 20]);
+
+    fn parse(trace: LocalizedTransactionTrace) -> ActionType {
+        let parser = parser(vec![], &[(NETWORK, ContractKind::Router(Protocol::Bancor))], &[]);
+        parser.classify(&trace).ty
+    }
+
+    #[test]
+    fn records_the_source_amount_paid_for_a_target() {
+        let input = IBancorNetwork::tradeByTargetAmountCall {
+            sourceToken: Address::from(TOKEN_A.0),
+            targetToken: Address::from(TOKEN_B.0),
+            targetAmount: U256::from(100),
+            maxSourceAmount: U256::from(110),
+            deadline: U256::MAX,
+            beneficiary: Address::ZERO,
+        }
+        .encode();
+        let trace = returning(call_trace(TRADER, NETWORK, input), word(105).to_vec());
+
+        let ActionType::Swap(swap) = parse(trace) else { panic!("expected a swap") };
+        assert_eq!(swap.recipient, Address::from(TRADER.0));
+        assert_eq!(
+            (swap.amount_in, swap.amount_out),
+            (Some(U256::from(105)), Some(U256::from(100)))
+        );
+    }
+
+    #[test]
+    fn records_withdrawn_amounts() {
+        let input = IBancorNetwork::withdrawCall { id: U256::from(7) }.encode();
+        let trace = returning(call_trace(TRADER, NETWORK, input), word(50).to_vec());
+
+        let ActionType::RemoveLiquidity(withdrawal) = parse(trace) else {
+            panic!("expected a withdrawal")
+        };
+        assert_eq!(withdrawal.pool, NETWORK);
+        assert_eq!(withdrawal.amounts, [U256::from(50)]);
+    }
+}
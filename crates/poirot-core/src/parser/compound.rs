@@ -0,0 +1,276 @@
+use super::{to_h160, Parser};
+use crate::{
+    action::{Action, ActionType, Liquidation, Protocol},
+    events::{DecodedLog, LogEvent},
+    tree::ActionNode,
+};
+use alloy_primitives::U256;
+use alloy_sol_types::{sol, SolCall};
+use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface ICErc20 {
+        function liquidateBorrow(
+            address borrower,
+            uint256 repayAmount,
+            address cTokenCollateral
+        ) external returns (uint256);
+        function seize(
+            address liquidator,
+            address borrower,
+            uint256 seizeTokens
+        ) external returns (uint256);
+        function exchangeRateStored() external view returns (uint256);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface ICEther {
+        function liquidateBorrow(address borrower, address cTokenCollateral) external payable;
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface IComet {
+        function absorb(address absorber, address[] calldata accounts) external;
+
+        event AbsorbDebt(
+            address indexed absorber,
+            address indexed borrower,
+            uint256 basePaidOut,
+            uint256 usdValue
+        );
+        event AbsorbCollateral(
+            address indexed absorber,
+            address indexed borrower,
+            address indexed asset,
+            uint256 collateralAbsorbed,
+            uint256 usdValue
+        );
+    }
+}
+
+impl Parser {
+    /// Parses a `liquidateBorrow` on a Compound V2 cToken, the market of the repaid debt. Its
+    /// underlying token is resolved from the pool registry; the cTokens seized are filled in from
+    /// the `seize` sub-call by [`complete_from_seize`].
+    pub fn parse_compound_v2(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let (borrower, debt_repaid, collateral) =
+            if let Ok(decoded) = ICErc20::liquidateBorrowCall::decode(&call.input, true) {
+                (decoded.borrower, Some(decoded.repayAmount), decoded.cTokenCollateral)
+            } else {
+                // cEther is repaid in ETH
+                let decoded = ICEther::liquidateBorrowCall::decode(&call.input, true).ok()?;
+                (decoded.borrower, Some(call.value), decoded.cTokenCollateral)
+            };
+
+        Some(Action::new(
+            ActionType::Liquidation(Liquidation {
+                protocol: Protocol::CompoundV2,
+                pool: call.to,
+                liquidator: call.from,
+                borrower,
+                debt_asset: self.pools.token(&call.to, 0, false),
+                debt_repaid,
+                collateral_asset: Some(to_h160(&collateral)),
+                collateral_seized: None,
+            }),
+            curr,
+        ))
+    }
+
+    /// Parses an `absorb` on a Compound V3 market. The protocol takes over the debt and
+    /// collateral of every account; the amounts are only emitted in the market's logs and are
+    /// filled in by [`complete_from_absorb`].
+    pub fn parse_compound_v3(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let decoded = IComet::absorbCall::decode(&call.input, true).ok()?;
+        let absorber = to_h160(&decoded.absorber);
+
+        let liquidations = decoded
+            .accounts
+            .into_iter()
+            .map(|borrower| Liquidation {
+                protocol: Protocol::CompoundV3,
+                pool: call.to,
+                liquidator: absorber,
+                borrower,
+                debt_asset: self.pools.token(&call.to, 0, false),
+                debt_repaid: None,
+                collateral_asset: None,
+                collateral_seized: None,
+            })
+            .collect();
+
+        Some(Action::new(ActionType::BatchLiquidation(liquidations), curr))
+    }
+}
+
+/// Fills in the cTokens seized by a Compound V2 liquidation from the `seize` call the debt market
+/// makes to the collateral market.
+pub(super) fn complete_from_seize(action: &mut Action, children: &[ActionNode]) {
+    let ActionType::Liquidation(liquidation) = &mut action.ty else { return };
+    if liquidation.protocol != Protocol::CompoundV2 || liquidation.collateral_seized.is_some() {
+        return
+    }
+
+    let seized = children.iter().find_map(|child| {
+        let ActionType::Unclassified(trace) = &child.action.ty else { return None };
+        let RethAction::Call(call) = &trace.trace.action else { return None };
+
+        (Some(call.to) == liquidation.collateral_asset)
+            .then(|| ICErc20::seizeCall::decode(&call.input, true).ok())
+            .flatten()
+    });
+
+    if let Some(seized) = seized {
+        liquidation.collateral_seized = Some(seized.seizeTokens);
+    }
+}
+
+/// Fills in the debt and collateral of the accounts of a Compound V3 `absorb` from the market's
+/// `AbsorbDebt` and `AbsorbCollateral` logs. An account is reported once per collateral asset
+/// absorbed, with its debt on the first of them.
+pub(super) fn complete_from_absorb(
+    action: &mut Action,
+    curr: &LocalizedTransactionTrace,
+    logs: &[DecodedLog],
+) {
+    let ActionType::BatchLiquidation(liquidations) = &mut action.ty else { return };
+    let Some(tx_index) = curr.transaction_position else { return };
+
+    let logs = logs.iter().filter(|log| log.transaction_index == tx_index as usize);
+    let mut completed = vec![];
+    for liquidation in liquidations.drain(..) {
+        if liquidation.protocol != Protocol::CompoundV3 {
+            completed.push(liquidation);
+            continue
+        }
+
+        let market = logs.clone().filter(|log| log.address == liquidation.pool);
+        let debt = market.clone().find_map(|log| match &log.event {
+            LogEvent::CometAbsorbDebt(absorbed) if absorbed.borrower == liquidation.borrower => {
+                Some(absorbed.basePaidOut)
+            }
+            _ => None,
+        });
+        let collateral: Vec<_> = market
+            .filter_map(|log| match &log.event {
+                LogEvent::CometAbsorbCollateral(absorbed)
+                    if absorbed.borrower == liquidation.borrower =>
+                {
+                    Some((to_h160(&absorbed.asset), absorbed.collateralAbsorbed))
+                }
+                _ => None,
+            })
+            .collect();
+
+        if collateral.is_empty() {
+            completed.push(Liquidation { debt_repaid: debt, ..liquidation });
+            continue
+        }
+
+        for (position, (asset, absorbed)) in collateral.into_iter().enumerate() {
+            completed.push(Liquidation {
+                debt_repaid: if position == 0 { debt } else { Some(U256::ZERO) },
+                collateral_asset: Some(asset),
+                collateral_seized: Some(absorbed),
+                ..liquidation.clone()
+            });
+        }
+    }
+
+    *liquidations = completed;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        classifier::ContractKind,
+        test_utils::{at, call_trace, parser},
+    };
+    use alloy_primitives::Address;
+    use reth_primitives::H160;
+
+    const MARKET: H160 = H160([1; 20]);
+    const ABSORBER: H160 = H160([2; 20]);
+    const WETH: H160 = H160([3; 20]);
+    const WBTC: H160 = H160([4; 20]);
+    const BORROWER: H160 = H160([5; 20]);
+    const OTHER_BORROWER: H160 = H160([6; 20]);
+
+    fn log(transaction_index: usize, address: H160, event: LogEvent) -> DecodedLog {
+        DecodedLog { transaction_index, log_index: 0, address, event }
+    }
+
+    fn absorbed_debt(borrower: H160, amount: u64) -> LogEvent {
+        LogEvent::CometAbsorbDebt(IComet::AbsorbDebt {
+            absorber: Address::from(ABSORBER.0),
+            borrower: Address::from(borrower.0),
+            basePaidOut: U256::from(amount),
+            usdValue: U256::ZERO,
+        })
+    }
+
+    fn absorbed_collateral(asset: H160, amount: u64) -> LogEvent {
+        LogEvent::CometAbsorbCollateral(IComet::AbsorbCollateral {
+            absorber: Address::from(ABSORBER.0),
+            borrower: Address::from(BORROWER.0),
+            asset: Address::from(asset.0),
+            collateralAbsorbed: U256::from(amount),
+            usdValue: U256::ZERO,
+        })
+    }
+
+    #[test]
+    fn fills_absorbs_from_the_market_logs() {
+        let input = IComet::absorbCall {
+            absorber: Address::from(ABSORBER.0),
+            accounts: vec![Address::from(BORROWER.0), Address::from(OTHER_BORROWER.0)],
+        }
+        .encode();
+        let trace = at(call_trace(ABSORBER, MARKET, input), 3, vec![], 0);
+        let parser = parser(vec![], &[(MARKET, ContractKind::Pool(Protocol::CompoundV3))], &[]);
+        let mut action = parser.classify(&trace);
+
+        let logs = vec![
+            log(3, MARKET, absorbed_debt(BORROWER, 1000)),
+            log(3, MARKET, absorbed_collateral(WETH, 1)),
+            log(3, MARKET, absorbed_collateral(WBTC, 2)),
+            log(3, MARKET, absorbed_debt(OTHER_BORROWER, 500)),
+            // Another transaction's absorb of the same account
+            log(4, MARKET, absorbed_debt(BORROWER, 7)),
+        ];
+        complete_from_absorb(&mut action, &trace, &logs);
+
+        let ActionType::BatchLiquidation(liquidations) = action.ty else {
+            panic!("expected a batch liquidation")
+        };
+        let absorbed: Vec<_> = liquidations
+            .iter()
+            .map(|liquidation| {
+                (
+                    to_h160(&liquidation.borrower),
+                    liquidation.debt_repaid,
+                    liquidation.collateral_asset,
+                    liquidation.collateral_seized,
+                )
+            })
+            .collect();
+        assert_eq!(
+            absorbed,
+            vec![
+                (BORROWER, Some(U256::from(1000)), Some(WETH), Some(U256::from(1))),
+                (BORROWER, Some(U256::ZERO), Some(WBTC), Some(U256::from(2))),
+                (OTHER_BORROWER, Some(U256::from(500)), None, None),
+            ]
+        );
+    }
+}
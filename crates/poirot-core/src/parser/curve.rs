@@ -0,0 +1,428 @@
+use super::{call_output, Parser};
+use crate::action::{Action, ActionType, AddLiquidity, Protocol, RemoveLiquidity, Swap};
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::{sol, SolInterface};
+use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
+
+/// The most coins a Curve pool holds.
+const MAX_COINS: usize = 8;
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface ICurveStableSwap {
+        function exchange(
+            int128 i,
+            int128 j,
+            uint256 dx,
+            uint256 min_dy
+        ) external returns (uint256);
+        function exchange_underlying(
+            int128 i,
+            int128 j,
+            uint256 dx,
+            uint256 min_dy
+        ) external returns (uint256);
+        function remove_liquidity_one_coin(
+            uint256 token_amount,
+            int128 i,
+            uint256 min_amount
+        ) external returns (uint256);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface ICurveCryptoSwap {
+        function exchange(
+            uint256 i,
+            uint256 j,
+            uint256 dx,
+            uint256 min_dy
+        ) external payable returns (uint256);
+        function exchange_underlying(
+            uint256 i,
+            uint256 j,
+            uint256 dx,
+            uint256 min_dy
+        ) external payable returns (uint256);
+        function remove_liquidity_one_coin(
+            uint256 token_amount,
+            uint256 i,
+            uint256 min_amount
+        ) external returns (uint256);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface ICurvePool2 {
+        function add_liquidity(
+            uint256[2] amounts,
+            uint256 min_mint_amount
+        ) external returns (uint256);
+        function remove_liquidity(
+            uint256 amount,
+            uint256[2] min_amounts
+        ) external returns (uint256[2]);
+        function remove_liquidity_imbalance(
+            uint256[2] amounts,
+            uint256 max_burn_amount
+        ) external returns (uint256);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface ICurvePool3 {
+        function add_liquidity(
+            uint256[3] amounts,
+            uint256 min_mint_amount
+        ) external returns (uint256);
+        function remove_liquidity(
+            uint256 amount,
+            uint256[3] min_amounts
+        ) external returns (uint256[3]);
+        function remove_liquidity_imbalance(
+            uint256[3] amounts,
+            uint256 max_burn_amount
+        ) external returns (uint256);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface ICurvePool4 {
+        function add_liquidity(
+            uint256[4] amounts,
+            uint256 min_mint_amount
+        ) external returns (uint256);
+        function remove_liquidity(
+            uint256 amount,
+            uint256[4] min_amounts
+        ) external returns (uint256[4]);
+        function remove_liquidity_imbalance(
+            uint256[4] amounts,
+            uint256 max_burn_amount
+        ) external returns (uint256);
+    }
+}
+
+/// A Curve call with the coin indices normalised across stableswap and cryptoswap pools.
+enum CurveCall {
+    Exchange { i: usize, j: usize, dx: U256, underlying: bool },
+    RemoveLiquidityOneCoin { token_amount: U256, i: usize, min_amount: U256 },
+    AddLiquidity { amounts: Vec<U256> },
+    RemoveLiquidity { amount: U256 },
+    RemoveLiquidityImbalance { amounts: Vec<U256> },
+}
+
+impl Parser {
+    /// Parses a call to a Curve stableswap or cryptoswap pool, resolving coin indices to token
+    /// addresses through the pool registry.
+    pub fn parse_curve(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        // Every pool returns a single uint256 (or nothing, for older pools) except
+        // `remove_liquidity`, which returns the withdrawn amounts
+        let returned = call_output(curr).map(decode_words).unwrap_or_default();
+        let returned_amount = returned.first().copied();
+
+        let ty = match decode_curve_call(&call.input)? {
+            CurveCall::Exchange { i, j, dx, underlying } => ActionType::Swap(Swap::new(
+                Protocol::Curve,
+                call.to,
+                call.from,
+                Address::from(call.from.0),
+                self.pools.token(&call.to, i, underlying),
+                self.pools.token(&call.to, j, underlying),
+                Some(dx),
+                returned_amount,
+            )),
+            CurveCall::RemoveLiquidityOneCoin { token_amount, i, min_amount } => {
+                // The index comes from calldata: it must be one of a known pool's coins, and for
+                // an unknown pool the amounts are only recorded if it can be a coin at all
+                let coins = match self.pools.get(&call.to) {
+                    Some(pool) if i >= pool.tokens.len() => return None,
+                    Some(pool) => Some(pool.tokens.len()),
+                    None => (i < MAX_COINS).then_some(i + 1),
+                };
+                let amounts = coins.map_or_else(Vec::new, |coins| {
+                    let mut amounts = vec![U256::ZERO; coins];
+                    amounts[i] = returned_amount.unwrap_or(min_amount);
+                    amounts
+                });
+
+                ActionType::RemoveLiquidity(RemoveLiquidity {
+                    protocol: Protocol::Curve,
+                    pool: call.to,
+                    recipient: Address::from(call.from.0),
+                    amounts,
+                    limits: returned_amount.is_none(),
+                    liquidity: Some(token_amount),
+                })
+            }
+            CurveCall::AddLiquidity { amounts } => ActionType::AddLiquidity(AddLiquidity {
+                protocol: Protocol::Curve,
+                pool: call.to,
+                recipient: Address::from(call.from.0),
+                amounts,
+                limits: false,
+                liquidity: returned_amount,
+            }),
+            CurveCall::RemoveLiquidity { amount } => ActionType::RemoveLiquidity(RemoveLiquidity {
+                protocol: Protocol::Curve,
+                pool: call.to,
+                recipient: Address::from(call.from.0),
+                amounts: returned,
+                limits: false,
+                liquidity: Some(amount),
+            }),
+            CurveCall::RemoveLiquidityImbalance { amounts } => {
+                ActionType::RemoveLiquidity(RemoveLiquidity {
+                    protocol: Protocol::Curve,
+                    pool: call.to,
+                    recipient: Address::from(call.from.0),
+                    amounts,
+                    limits: false,
+                    liquidity: returned_amount,
+                })
+            }
+        };
+
+        Some(Action::new(ty, curr))
+    }
+}
+
+fn decode_curve_call(input: &[u8]) -> Option<CurveCall> {
+    if let Ok(decoded) = ICurveStableSwap::ICurveStableSwapCalls::decode(input, true) {
+        return Some(match decoded {
+            ICurveStableSwap::ICurveStableSwapCalls::exchange(exchange_call) => {
+                CurveCall::Exchange {
+                    i: usize::try_from(exchange_call.i).ok()?,
+                    j: usize::try_from(exchange_call.j).ok()?,
+                    dx: exchange_call.dx,
+                    underlying: false,
+                }
+            }
+            ICurveStableSwap::ICurveStableSwapCalls::exchange_underlying(exchange_call) => {
+                CurveCall::Exchange {
+                    i: usize::try_from(exchange_call.i).ok()?,
+                    j: usize::try_from(exchange_call.j).ok()?,
+                    dx: exchange_call.dx,
+                    underlying: true,
+                }
+            }
+            ICurveStableSwap::ICurveStableSwapCalls::remove_liquidity_one_coin(remove_call) => {
+                CurveCall::RemoveLiquidityOneCoin {
+                    token_amount: remove_call.token_amount,
+                    i: usize::try_from(remove_call.i).ok()?,
+                    min_amount: remove_call.min_amount,
+                }
+            }
+        })
+    }
+
+    if let Ok(decoded) = ICurveCryptoSwap::ICurveCryptoSwapCalls::decode(input, true) {
+        return Some(match decoded {
+            ICurveCryptoSwap::ICurveCryptoSwapCalls::exchange(exchange_call) => {
+                CurveCall::Exchange {
+                    i: usize::try_from(exchange_call.i).ok()?,
+                    j: usize::try_from(exchange_call.j).ok()?,
+                    dx: exchange_call.dx,
+                    underlying: false,
+                }
+            }
+            ICurveCryptoSwap::ICurveCryptoSwapCalls::exchange_underlying(exchange_call) => {
+                CurveCall::Exchange {
+                    i: usize::try_from(exchange_call.i).ok()?,
+                    j: usize::try_from(exchange_call.j).ok()?,
+                    dx: exchange_call.dx,
+                    underlying: true,
+                }
+            }
+            ICurveCryptoSwap::ICurveCryptoSwapCalls::remove_liquidity_one_coin(remove_call) => {
+                CurveCall::RemoveLiquidityOneCoin {
+                    token_amount: remove_call.token_amount,
+                    i: usize::try_from(remove_call.i).ok()?,
+                    min_amount: remove_call.min_amount,
+                }
+            }
+        })
+    }
+
+    if let Ok(decoded) = ICurvePool2::ICurvePool2Calls::decode(input, true) {
+        return Some(match decoded {
+            ICurvePool2::ICurvePool2Calls::add_liquidity(add_call) => {
+                CurveCall::AddLiquidity { amounts: add_call.amounts.to_vec() }
+            }
+            ICurvePool2::ICurvePool2Calls::remove_liquidity(remove_call) => {
+                CurveCall::RemoveLiquidity { amount: remove_call.amount }
+            }
+            ICurvePool2::ICurvePool2Calls::remove_liquidity_imbalance(remove_call) => {
+                CurveCall::RemoveLiquidityImbalance { amounts: remove_call.amounts.to_vec() }
+            }
+        })
+    }
+
+    if let Ok(decoded) = ICurvePool3::ICurvePool3Calls::decode(input, true) {
+        return Some(match decoded {
+            ICurvePool3::ICurvePool3Calls::add_liquidity(add_call) => {
+                CurveCall::AddLiquidity { amounts: add_call.amounts.to_vec() }
+            }
+            ICurvePool3::ICurvePool3Calls::remove_liquidity(remove_call) => {
+                CurveCall::RemoveLiquidity { amount: remove_call.amount }
+            }
+            ICurvePool3::ICurvePool3Calls::remove_liquidity_imbalance(remove_call) => {
+                CurveCall::RemoveLiquidityImbalance { amounts: remove_call.amounts.to_vec() }
+            }
+        })
+    }
+
+    if let Ok(decoded) = ICurvePool4::ICurvePool4Calls::decode(input, true) {
+        return Some(match decoded {
+            ICurvePool4::ICurvePool4Calls::add_liquidity(add_call) => {
+                CurveCall::AddLiquidity { amounts: add_call.amounts.to_vec() }
+            }
+            ICurvePool4::ICurvePool4Calls::remove_liquidity(remove_call) => {
+                CurveCall::RemoveLiquidity { amount: remove_call.amount }
+            }
+            ICurvePool4::ICurvePool4Calls::remove_liquidity_imbalance(remove_call) => {
+                CurveCall::RemoveLiquidityImbalance { amounts: remove_call.amounts.to_vec() }
+            }
+        })
+    }
+
+    None
+}
+
+/// Splits return data made only of static 32-byte words into `uint256`s.
+fn decode_words(output: &[u8]) -> Vec<U256> {
+    output.chunks_exact(32).map(U256::from_be_slice).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        classifier::ContractKind,
+        test_utils::{call_trace, parser, pool, returning, word},
+    };
+    use alloy_sol_types::SolCall;
+    use reth_primitives::H160;
+
+    const POOL: H160 = H160([1; 20]);
+    const UNKNOWN_POOL: H160 = H160([2; 20]);
+    const TRADER: H160 = H160([3; 20]);
+    const DAI: H160 = H160([4; 20]);
+    const USDC: H160 = H160([5; 20]);
+    const USDT: H160 = H160([6; 20]);
+
+    fn parse(trace: LocalizedTransactionTrace) -> ActionType {
+        let parser = parser(
+            vec![],
+            &[
+                (POOL, ContractKind::Pool(Protocol::Curve)),
+                (UNKNOWN_POOL, ContractKind::Pool(Protocol::Curve)),
+            ],
+            &[(POOL, pool(Protocol::Curve, &[DAI, USDC, USDT]))],
+        );
+        parser.classify(&trace).ty
+    }
+
+    #[test]
+    fn resolves_exchanged_coins() {
+        let input = ICurveStableSwap::exchangeCall {
+            i: 0,
+            j: 2,
+            dx: U256::from(100),
+            min_dy: U256::from(99),
+        }
+        .encode();
+
+        let trace = returning(call_trace(TRADER, POOL, input.clone()), word(98).to_vec());
+        let ActionType::Swap(swap) = parse(trace) else { panic!("expected a swap") };
+        assert_eq!((swap.pool, swap.token_in, swap.token_out), (POOL, Some(DAI), Some(USDT)));
+        assert_eq!(
+            (swap.amount_in, swap.amount_out),
+            (Some(U256::from(100)), Some(U256::from(98)))
+        );
+
+        let ActionType::Swap(swap) = parse(call_trace(TRADER, UNKNOWN_POOL, input)) else {
+            panic!("expected a swap")
+        };
+        assert_eq!((swap.token_in, swap.token_out), (None, None));
+    }
+
+    #[test]
+    fn leaves_unknown_underlying_coins_unresolved() {
+        let input = ICurveCryptoSwap::exchange_underlyingCall {
+            i: U256::from(1),
+            j: U256::from(0),
+            dx: U256::from(100),
+            min_dy: U256::from(99),
+        }
+        .encode();
+
+        let ActionType::Swap(swap) = parse(call_trace(TRADER, POOL, input)) else {
+            panic!("expected a swap")
+        };
+        assert_eq!((swap.token_in, swap.token_out), (None, None));
+    }
+
+    #[test]
+    fn parses_single_coin_withdrawals() {
+        let input = ICurveStableSwap::remove_liquidity_one_coinCall {
+            token_amount: U256::from(60),
+            i: 1,
+            min_amount: U256::from(49),
+        }
+        .encode();
+        let trace = returning(call_trace(TRADER, POOL, input), word(50).to_vec());
+
+        let ActionType::RemoveLiquidity(removal) = parse(trace) else {
+            panic!("expected a liquidity removal")
+        };
+        assert_eq!(removal.amounts, [U256::ZERO, U256::from(50), U256::ZERO]);
+        assert_eq!(removal.liquidity, Some(U256::from(60)));
+    }
+
+    #[test]
+    fn bounds_withdrawn_coin_indices() {
+        let withdraw = |pool, i| {
+            let input = ICurveStableSwap::remove_liquidity_one_coinCall {
+                token_amount: U256::from(60),
+                i,
+                min_amount: U256::from(49),
+            }
+            .encode();
+            parse(call_trace(TRADER, pool, input))
+        };
+
+        assert!(matches!(withdraw(POOL, 3), ActionType::Unclassified(_)));
+
+        let ActionType::RemoveLiquidity(removal) = withdraw(UNKNOWN_POOL, 1) else {
+            panic!("expected a liquidity removal")
+        };
+        assert_eq!(removal.amounts, [U256::ZERO, U256::from(49)]);
+
+        let ActionType::RemoveLiquidity(removal) = withdraw(UNKNOWN_POOL, 1 << 40) else {
+            panic!("expected a liquidity removal")
+        };
+        assert!(removal.amounts.is_empty());
+        assert_eq!(removal.liquidity, Some(U256::from(60)));
+    }
+
+    #[test]
+    fn parses_deposits() {
+        let input = ICurvePool3::add_liquidityCall {
+            amounts: [U256::from(1), U256::from(2), U256::from(3)],
+            min_mint_amount: U256::ZERO,
+        }
+        .encode();
+        let trace = returning(call_trace(TRADER, POOL, input), word(6).to_vec());
+
+        let ActionType::AddLiquidity(deposit) = parse(trace) else { panic!("expected a deposit") };
+        assert_eq!(deposit.amounts, [U256::from(1), U256::from(2), U256::from(3)]);
+        assert_eq!(deposit.liquidity, Some(U256::from(6)));
+    }
+}
@@ -0,0 +1,435 @@
+use super::{call_output, to_h160, Parser};
+use crate::action::{Action, ActionType, AddLiquidity, Protocol, RemoveLiquidity, Swap};
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::{sol, SolCall, SolInterface};
+use reth_primitives::H160;
+use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface IDodoV1Pair {
+        function sellBaseToken(
+            uint256 amount,
+            uint256 minReceiveQuote,
+            bytes data
+        ) external returns (uint256);
+        function buyBaseToken(
+            uint256 amount,
+            uint256 maxPayQuote,
+            bytes data
+        ) external returns (uint256);
+        function depositBase(uint256 amount) external returns (uint256);
+        function depositQuote(uint256 amount) external returns (uint256);
+        function withdrawBase(uint256 amount) external returns (uint256);
+        function withdrawQuote(uint256 amount) external returns (uint256);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface IDodoV2Pool {
+        function sellBase(address to) external returns (uint256 receiveQuoteAmount);
+        function sellQuote(address to) external returns (uint256 receiveBaseAmount);
+        function buyShares(address to) external returns (
+            uint256 shares,
+            uint256 baseInput,
+            uint256 quoteInput
+        );
+        function sellShares(
+            uint256 shareAmount,
+            address to,
+            uint256 baseMinAmount,
+            uint256 quoteMinAmount,
+            bytes data,
+            uint256 deadline
+        ) external returns (uint256 baseAmount, uint256 quoteAmount);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface IDodoV3Pool {
+        function sellToken(
+            address to,
+            address fromToken,
+            address toToken,
+            uint256 fromAmount,
+            uint256 minReceiveAmount,
+            bytes data
+        ) external returns (uint256);
+        function buyToken(
+            address to,
+            address fromToken,
+            address toToken,
+            uint256 quoteAmount,
+            uint256 maxPayAmount,
+            bytes data
+        ) external returns (uint256);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface IDodoV3Vault {
+        function userDeposit(address user, address token) external returns (uint256 dTokenAmount);
+        function userWithdraw(
+            address to,
+            address user,
+            address token,
+            uint256 dTokenAmount
+        ) external returns (uint256 amount);
+    }
+}
+
+impl Parser {
+    /// Parses a call to a DODO V1 pair. The pair's tokens are resolved from the pool registry as
+    /// `[base, quote]`.
+    pub fn parse_dodo_v1(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
+        use IDodoV1Pair::IDodoV1PairCalls as Calls;
+
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let decoded = Calls::decode(&call.input, true).ok()?;
+        let output = call_output(curr);
+
+        let base = self.pools.token(&call.to, 0, false);
+        let quote = self.pools.token(&call.to, 1, false);
+        let trader = Address::from(call.from.0);
+
+        let ty = match decoded {
+            Calls::sellBaseToken(sell_call) => {
+                let received = output.and_then(|output| {
+                    IDodoV1Pair::sellBaseTokenCall::decode_returns(output, true)
+                        .ok()
+                        .map(|sold| sold._0)
+                });
+                ActionType::Swap(Swap::new(
+                    Protocol::Dodo,
+                    call.to,
+                    call.from,
+                    trader,
+                    base,
+                    quote,
+                    Some(sell_call.amount),
+                    received,
+                ))
+            }
+            Calls::buyBaseToken(buy_call) => {
+                let paid = output.and_then(|output| {
+                    IDodoV1Pair::buyBaseTokenCall::decode_returns(output, true)
+                        .ok()
+                        .map(|bought| bought._0)
+                });
+                ActionType::Swap(Swap::new(
+                    Protocol::Dodo,
+                    call.to,
+                    call.from,
+                    trader,
+                    quote,
+                    base,
+                    paid,
+                    Some(buy_call.amount),
+                ))
+            }
+            // Deposits return the capital tokens minted for the deposit
+            Calls::depositBase(deposit_call) => {
+                let minted = output.and_then(|output| {
+                    IDodoV1Pair::depositBaseCall::decode_returns(output, true)
+                        .ok()
+                        .map(|deposited| deposited._0)
+                });
+                dodo_add_liquidity(call.to, trader, [deposit_call.amount, U256::ZERO], minted)
+            }
+            Calls::depositQuote(deposit_call) => {
+                let minted = output.and_then(|output| {
+                    IDodoV1Pair::depositQuoteCall::decode_returns(output, true)
+                        .ok()
+                        .map(|deposited| deposited._0)
+                });
+                dodo_add_liquidity(call.to, trader, [U256::ZERO, deposit_call.amount], minted)
+            }
+            // Withdrawals return the amount paid out, which is the requested amount less any
+            // penalty
+            Calls::withdrawBase(withdraw_call) => {
+                let withdrawn = output
+                    .and_then(|output| {
+                        IDodoV1Pair::withdrawBaseCall::decode_returns(output, true).ok()
+                    })
+                    .map_or(withdraw_call.amount, |withdrawn| withdrawn._0);
+                dodo_remove_liquidity(call.to, trader, [withdrawn, U256::ZERO])
+            }
+            Calls::withdrawQuote(withdraw_call) => {
+                let withdrawn = output
+                    .and_then(|output| {
+                        IDodoV1Pair::withdrawQuoteCall::decode_returns(output, true).ok()
+                    })
+                    .map_or(withdraw_call.amount, |withdrawn| withdrawn._0);
+                dodo_remove_liquidity(call.to, trader, [U256::ZERO, withdrawn])
+            }
+        };
+
+        Some(Action::new(ty, curr))
+    }
+
+    /// Parses a call to a DODO V2 vending machine, private or stable pool. Like UniswapV2, sells
+    /// and share purchases pay with tokens transferred to the pool beforehand.
+    pub fn parse_dodo_v2(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
+        use IDodoV2Pool::IDodoV2PoolCalls as Calls;
+
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let decoded = Calls::decode(&call.input, true).ok()?;
+        let output = call_output(curr);
+
+        let base = self.pools.token(&call.to, 0, false);
+        let quote = self.pools.token(&call.to, 1, false);
+
+        let ty = match decoded {
+            Calls::sellBase(sell_call) => {
+                let received = output.and_then(|output| {
+                    IDodoV2Pool::sellBaseCall::decode_returns(output, true)
+                        .ok()
+                        .map(|sold| sold.receiveQuoteAmount)
+                });
+                ActionType::Swap(Swap::new(
+                    Protocol::DodoV2,
+                    call.to,
+                    call.from,
+                    sell_call.to,
+                    base,
+                    quote,
+                    None,
+                    received,
+                ))
+            }
+            Calls::sellQuote(sell_call) => {
+                let received = output.and_then(|output| {
+                    IDodoV2Pool::sellQuoteCall::decode_returns(output, true)
+                        .ok()
+                        .map(|sold| sold.receiveBaseAmount)
+                });
+                ActionType::Swap(Swap::new(
+                    Protocol::DodoV2,
+                    call.to,
+                    call.from,
+                    sell_call.to,
+                    quote,
+                    base,
+                    None,
+                    received,
+                ))
+            }
+            Calls::buyShares(buy_call) => {
+                let bought = output.and_then(|output| {
+                    IDodoV2Pool::buySharesCall::decode_returns(output, true).ok()
+                });
+
+                ActionType::AddLiquidity(AddLiquidity {
+                    protocol: Protocol::DodoV2,
+                    pool: call.to,
+                    recipient: buy_call.to,
+                    amounts: bought
+                        .as_ref()
+                        .map(|bought| vec![bought.baseInput, bought.quoteInput])
+                        .unwrap_or_default(),
+                    limits: false,
+                    liquidity: bought.map(|bought| bought.shares),
+                })
+            }
+            Calls::sellShares(sell_call) => {
+                let sold = output.and_then(|output| {
+                    IDodoV2Pool::sellSharesCall::decode_returns(output, true).ok()
+                });
+
+                ActionType::RemoveLiquidity(RemoveLiquidity {
+                    protocol: Protocol::DodoV2,
+                    pool: call.to,
+                    recipient: sell_call.to,
+                    limits: sold.is_none(),
+                    amounts: sold
+                        .map(|sold| vec![sold.baseAmount, sold.quoteAmount])
+                        .unwrap_or_else(|| vec![sell_call.baseMinAmount, sell_call.quoteMinAmount]),
+                    liquidity: Some(sell_call.shareAmount),
+                })
+            }
+        };
+
+        Some(Action::new(ty, curr))
+    }
+
+    /// Parses a swap on a DODO V3 pool or a deposit/withdrawal on the DODO V3 vault.
+    pub fn parse_dodo_v3(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let output = call_output(curr);
+
+        let ty = if let Ok(decoded) = IDodoV3Pool::IDodoV3PoolCalls::decode(&call.input, true) {
+            match decoded {
+                IDodoV3Pool::IDodoV3PoolCalls::sellToken(sell_call) => {
+                    let received = output.and_then(|output| {
+                        IDodoV3Pool::sellTokenCall::decode_returns(output, true)
+                            .ok()
+                            .map(|sold| sold._0)
+                    });
+                    ActionType::Swap(Swap::new(
+                        Protocol::DodoV3,
+                        call.to,
+                        call.from,
+                        sell_call.to,
+                        Some(to_h160(&sell_call.fromToken)),
+                        Some(to_h160(&sell_call.toToken)),
+                        Some(sell_call.fromAmount),
+                        received,
+                    ))
+                }
+                IDodoV3Pool::IDodoV3PoolCalls::buyToken(buy_call) => {
+                    let paid = output.and_then(|output| {
+                        IDodoV3Pool::buyTokenCall::decode_returns(output, true)
+                            .ok()
+                            .map(|bought| bought._0)
+                    });
+                    ActionType::Swap(Swap::new(
+                        Protocol::DodoV3,
+                        call.to,
+                        call.from,
+                        buy_call.to,
+                        Some(to_h160(&buy_call.fromToken)),
+                        Some(to_h160(&buy_call.toToken)),
+                        paid,
+                        Some(buy_call.quoteAmount),
+                    ))
+                }
+            }
+        } else {
+            // Vault deposits and withdrawals are single-token; the pool is the vault and the
+            // liquidity is the amount of dTokens minted or burned
+            match IDodoV3Vault::IDodoV3VaultCalls::decode(&call.input, true).ok()? {
+                IDodoV3Vault::IDodoV3VaultCalls::userDeposit(deposit_call) => {
+                    ActionType::AddLiquidity(AddLiquidity {
+                        protocol: Protocol::DodoV3,
+                        pool: call.to,
+                        recipient: deposit_call.user,
+                        amounts: vec![],
+                        limits: false,
+                        liquidity: output.and_then(|output| {
+                            IDodoV3Vault::userDepositCall::decode_returns(output, true)
+                                .ok()
+                                .map(|deposited| deposited.dTokenAmount)
+                        }),
+                    })
+                }
+                IDodoV3Vault::IDodoV3VaultCalls::userWithdraw(withdraw_call) => {
+                    ActionType::RemoveLiquidity(RemoveLiquidity {
+                        protocol: Protocol::DodoV3,
+                        pool: call.to,
+                        recipient: withdraw_call.to,
+                        amounts: output
+                            .and_then(|output| {
+                                IDodoV3Vault::userWithdrawCall::decode_returns(output, true).ok()
+                            })
+                            .map(|withdrawn| vec![withdrawn.amount])
+                            .unwrap_or_default(),
+                        limits: false,
+                        liquidity: Some(withdraw_call.dTokenAmount),
+                    })
+                }
+            }
+        };
+
+        Some(Action::new(ty, curr))
+    }
+}
+
+fn dodo_add_liquidity(
+    pool: H160,
+    recipient: Address,
+    amounts: [U256; 2],
+    liquidity: Option<U256>,
+) -> ActionType {
+    ActionType::AddLiquidity(AddLiquidity {
+        protocol: Protocol::Dodo,
+        pool,
+        recipient,
+        amounts: amounts.to_vec(),
+        limits: false,
+        liquidity,
+    })
+}
+
+fn dodo_remove_liquidity(pool: H160, recipient: Address, amounts: [U256; 2]) -> ActionType {
+    ActionType::RemoveLiquidity(RemoveLiquidity {
+        protocol: Protocol::Dodo,
+        pool,
+        recipient,
+        amounts: amounts.to_vec(),
+        limits: false,
+        liquidity: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        classifier::ContractKind,
+        test_utils::{call_trace, parser, returning, word},
+    };
+
+    const PAIR: H160 = H160([1; 20]);
+    const VAULT: H160 = H160([2; 20]);
+    const TRADER: H160 = H160([3; 20]);
+    const TOKEN: H160 = H160([4; 20]);
+
+    fn parse(trace: LocalizedTransactionTrace) -> ActionType {
+        let parser = parser(
+            vec![],
+            &[
+                (PAIR, ContractKind::Pool(Protocol::Dodo)),
+                (VAULT, ContractKind::Vault(Protocol::DodoV3)),
+            ],
+            &[],
+        );
+        parser.classify(&trace).ty
+    }
+
+    #[test]
+    fn records_minted_capital_as_liquidity() {
+        let input = IDodoV1Pair::depositQuoteCall { amount: U256::from(100) }.encode();
+        let trace = returning(call_trace(TRADER, PAIR, input), word(90).to_vec());
+
+        let ActionType::AddLiquidity(deposit) = parse(trace) else { panic!("expected a deposit") };
+        assert_eq!(deposit.amounts, [U256::ZERO, U256::from(100)]);
+        assert_eq!(deposit.liquidity, Some(U256::from(90)));
+    }
+
+    #[test]
+    fn records_withdrawals_less_the_penalty() {
+        let input = IDodoV1Pair::withdrawBaseCall { amount: U256::from(100) }.encode();
+        let trace = returning(call_trace(TRADER, PAIR, input.clone()), word(98).to_vec());
+
+        let ActionType::RemoveLiquidity(withdrawal) = parse(trace) else {
+            panic!("expected a withdrawal")
+        };
+        assert_eq!(withdrawal.amounts, [U256::from(98), U256::ZERO]);
+        assert_eq!(withdrawal.liquidity, None);
+
+        let ActionType::RemoveLiquidity(withdrawal) = parse(call_trace(TRADER, PAIR, input)) else {
+            panic!("expected a withdrawal")
+        };
+        assert_eq!(withdrawal.amounts, [U256::from(100), U256::ZERO]);
+    }
+
+    #[test]
+    fn reports_the_vault_as_the_pool() {
+        let input = IDodoV3Vault::userDepositCall {
+            user: Address::from(TRADER.0),
+            token: Address::from(TOKEN.0),
+        }
+        .encode();
+        let trace = returning(call_trace(TRADER, VAULT, input), word(40).to_vec());
+
+        let ActionType::AddLiquidity(deposit) = parse(trace) else { panic!("expected a deposit") };
+        assert_eq!(deposit.pool, VAULT);
+        assert_eq!(deposit.liquidity, Some(U256::from(40)));
+    }
+}
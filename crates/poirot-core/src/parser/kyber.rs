@@ -0,0 +1,145 @@
+use super::{call_output, to_h160, Parser};
+use crate::action::{Action, ActionType, Protocol, Swap};
+use alloy_sol_types::{sol, SolCall, SolInterface};
+use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface IKyberNetworkProxy {
+        function trade(
+            address src,
+            uint256 srcAmount,
+            address dest,
+            address destAddress,
+            uint256 maxDestAmount,
+            uint256 minConversionRate,
+            address platformWallet
+        ) external payable returns (uint256);
+        function tradeWithHint(
+            address src,
+            uint256 srcAmount,
+            address dest,
+            address destAddress,
+            uint256 maxDestAmount,
+            uint256 minConversionRate,
+            address walletId,
+            bytes hint
+        ) external payable returns (uint256);
+        function tradeWithHintAndFee(
+            address src,
+            uint256 srcAmount,
+            address dest,
+            address destAddress,
+            uint256 maxDestAmount,
+            uint256 minConversionRate,
+            address platformWallet,
+            uint256 platformFeeBps,
+            bytes hint
+        ) external payable returns (uint256 destAmount);
+    }
+}
+
+impl Parser {
+    /// Parses a trade through the Kyber network proxy. The reserves a trade is matched against
+    /// are chosen inside the network, so the proxy is reported as the pool. Kyber DMM pools use
+    /// the UniswapV2 pair interface and are parsed by [`Parser::parse_uniswap_v2_pair`].
+    pub fn parse_kyber_proxy(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
+        use IKyberNetworkProxy::IKyberNetworkProxyCalls as Calls;
+
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let output = call_output(curr);
+        // Every variant returns the amount of `dest` received
+        let (src, src_amount, dest, dest_address, amount_out) =
+            match Calls::decode(&call.input, true).ok()? {
+                Calls::trade(trade_call) => (
+                    trade_call.src,
+                    trade_call.srcAmount,
+                    trade_call.dest,
+                    trade_call.destAddress,
+                    output.and_then(|output| {
+                        IKyberNetworkProxy::tradeCall::decode_returns(output, true)
+                            .ok()
+                            .map(|traded| traded._0)
+                    }),
+                ),
+                Calls::tradeWithHint(trade_call) => (
+                    trade_call.src,
+                    trade_call.srcAmount,
+                    trade_call.dest,
+                    trade_call.destAddress,
+                    output.and_then(|output| {
+                        IKyberNetworkProxy::tradeWithHintCall::decode_returns(output, true)
+                            .ok()
+                            .map(|traded| traded._0)
+                    }),
+                ),
+                Calls::tradeWithHintAndFee(trade_call) => (
+                    trade_call.src,
+                    trade_call.srcAmount,
+                    trade_call.dest,
+                    trade_call.destAddress,
+                    output.and_then(|output| {
+                        IKyberNetworkProxy::tradeWithHintAndFeeCall::decode_returns(output, true)
+                            .ok()
+                            .map(|traded| traded.destAmount)
+                    }),
+                ),
+            };
+
+        Some(Action::new(
+            ActionType::Swap(Swap::new(
+                Protocol::Kyber,
+                call.to,
+                call.from,
+                dest_address,
+                Some(to_h160(&src)),
+                Some(to_h160(&dest)),
+                Some(src_amount),
+                amount_out,
+            )),
+            curr,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        classifier::ContractKind,
+        test_utils::{call_trace, parser, returning, word},
+    };
+    use alloy_primitives::{Address, U256};
+    use reth_primitives::H160;
+
+    const PROXY: H160 = H160([1; 20]);
+    const TRADER: H160 = H160([2; 20]);
+    const TOKEN_A: H160 = H160([3; 20]);
+    const TOKEN_B: H160 = H160([4; 20]);
+
+    #[test]
+    fn records_the_destination_amount_of_fee_trades() {
+        let parser = parser(vec![], &[(PROXY, ContractKind::Router(Protocol::Kyber))], &[]);
+        let input = IKyberNetworkProxy::tradeWithHintAndFeeCall {
+            src: Address::from(TOKEN_A.0),
+            srcAmount: U256::from(100),
+            dest: Address::from(TOKEN_B.0),
+            destAddress: Address::from(TRADER.0),
+            maxDestAmount: U256::MAX,
+            minConversionRate: U256::ZERO,
+            platformWallet: Address::ZERO,
+            platformFeeBps: U256::from(25),
+            hint: vec![],
+        }
+        .encode();
+        let trace = returning(call_trace(TRADER, PROXY, input), word(95).to_vec());
+
+        let ActionType::Swap(swap) = parser.classify(&trace).ty else { panic!("expected a swap") };
+        assert_eq!((swap.pool, swap.token_out), (PROXY, Some(TOKEN_B)));
+        assert_eq!(
+            (swap.amount_in, swap.amount_out),
+            (Some(U256::from(100)), Some(U256::from(95)))
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use super::{call_output, to_h160, Parser};
+use crate::action::{Action, ActionType, AddLiquidity, Protocol, RemoveLiquidity, Swap};
+use alloy_primitives::Address;
+use alloy_sol_types::{sol, SolCall, SolInterface};
+use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface IMooniswap {
+        function swap(
+            address src,
+            address dst,
+            uint256 amount,
+            uint256 minReturn,
+            address referral
+        ) external payable returns (uint256 result);
+        function swapFor(
+            address src,
+            address dst,
+            uint256 amount,
+            uint256 minReturn,
+            address referral,
+            address receiver
+        ) external payable returns (uint256 result);
+        function deposit(
+            uint256[2] maxAmounts,
+            uint256[2] minAmounts
+        ) external payable returns (uint256 fairSupply, uint256[2] receivedAmounts);
+        function withdraw(
+            uint256 amount,
+            uint256[] minReturns
+        ) external returns (uint256[2] withdrawnAmounts);
+    }
+}
+
+impl Parser {
+    /// Parses a call to a Mooniswap pool.
+    pub fn parse_mooniswap(&self, curr: &LocalizedTransactionTrace) -> Option<Action> {
+        use IMooniswap::IMooniswapCalls as Calls;
+
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let decoded = Calls::decode(&call.input, true).ok()?;
+        let output = call_output(curr);
+
+        let ty = match decoded {
+            Calls::swap(swap_call) => ActionType::Swap(Swap::new(
+                Protocol::Mooniswap,
+                call.to,
+                call.from,
+                Address::from(call.from.0),
+                Some(to_h160(&swap_call.src)),
+                Some(to_h160(&swap_call.dst)),
+                Some(swap_call.amount),
+                output.and_then(|output| {
+                    IMooniswap::swapCall::decode_returns(output, true)
+                        .ok()
+                        .map(|swapped| swapped.result)
+                }),
+            )),
+            Calls::swapFor(swap_call) => ActionType::Swap(Swap::new(
+                Protocol::Mooniswap,
+                call.to,
+                call.from,
+                swap_call.receiver,
+                Some(to_h160(&swap_call.src)),
+                Some(to_h160(&swap_call.dst)),
+                Some(swap_call.amount),
+                output.and_then(|output| {
+                    IMooniswap::swapForCall::decode_returns(output, true)
+                        .ok()
+                        .map(|swapped| swapped.result)
+                }),
+            )),
+            Calls::deposit(deposit_call) => {
+                let deposited = output
+                    .and_then(|output| IMooniswap::depositCall::decode_returns(output, true).ok());
+
+                ActionType::AddLiquidity(AddLiquidity {
+                    protocol: Protocol::Mooniswap,
+                    pool: call.to,
+                    recipient: Address::from(call.from.0),
+                    limits: deposited.is_none(),
+                    amounts: deposited
+                        .as_ref()
+                        .map_or(deposit_call.maxAmounts, |deposited| deposited.receivedAmounts)
+                        .to_vec(),
+                    liquidity: deposited.map(|deposited| deposited.fairSupply),
+                })
+            }
+            Calls::withdraw(withdraw_call) => ActionType::RemoveLiquidity(RemoveLiquidity {
+                protocol: Protocol::Mooniswap,
+                pool: call.to,
+                recipient: Address::from(call.from.0),
+                amounts: output
+                    .and_then(|output| IMooniswap::withdrawCall::decode_returns(output, true).ok())
+                    .map(|withdrawn| withdrawn.withdrawnAmounts.to_vec())
+                    .unwrap_or_default(),
+                limits: false,
+                liquidity: Some(withdraw_call.amount),
+            }),
+        };
+
+        Some(Action::new(ty, curr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        classifier::ContractKind,
+        test_utils::{call_trace, parser, returning, word},
+    };
+    use alloy_primitives::U256;
+    use reth_primitives::H160;
+
+    const POOL: H160 = H160([1; 20]);
+    const TRADER: H160 = H160([2; 20]);
+    const RECEIVER: H160 = H160([3; 20]);
+    const TOKEN_A: H160 = H160([4; 20]);
+    const TOKEN_B: H160 = H160([5; 20]);
+
+    fn parse(trace: LocalizedTransactionTrace) -> ActionType {
+        let parser = parser(vec![], &[(POOL, ContractKind::Pool(Protocol::Mooniswap))], &[]);
+        parser.classify(&trace).ty
+    }
+
+    #[test]
+    fn parses_swaps_for_a_receiver() {
+        let input = IMooniswap::swapForCall {
+            src: Address::from(TOKEN_A.0),
+            dst: Address::from(TOKEN_B.0),
+            amount: U256::from(100),
+            minReturn: U256::from(90),
+            referral: Address::ZERO,
+            receiver: Address::from(RECEIVER.0),
+        }
+        .encode();
+        let trace = returning(call_trace(TRADER, POOL, input), word(95).to_vec());
+
+        let ActionType::Swap(swap) = parse(trace) else { panic!("expected a swap") };
+        assert_eq!(swap.recipient, Address::from(RECEIVER.0));
+        assert_eq!((swap.token_in, swap.token_out), (Some(TOKEN_A), Some(TOKEN_B)));
+        assert_eq!(
+            (swap.amount_in, swap.amount_out),
+            (Some(U256::from(100)), Some(U256::from(95)))
+        );
+    }
+
+    #[test]
+    fn records_received_deposit_amounts() {
+        let input = IMooniswap::depositCall {
+            maxAmounts: [U256::from(100), U256::from(200)],
+            minAmounts: [U256::ZERO, U256::ZERO],
+        }
+        .encode();
+        let output = [word(150), word(100), word(180)].concat();
+
+        let ActionType::AddLiquidity(deposit) =
+            parse(returning(call_trace(TRADER, POOL, input.clone()), output))
+        else {
+            panic!("expected a deposit")
+        };
+        assert_eq!(deposit.amounts, [U256::from(100), U256::from(180)]);
+        assert_eq!(deposit.liquidity, Some(U256::from(150)));
+        assert!(!deposit.limits);
+
+        let ActionType::AddLiquidity(deposit) = parse(call_trace(TRADER, POOL, input)) else {
+            panic!("expected a deposit")
+        };
+        assert_eq!(deposit.amounts, [U256::from(100), U256::from(200)]);
+        assert_eq!(deposit.liquidity, None);
+        assert!(deposit.limits);
+    }
+}
@@ -0,0 +1,339 @@
+use super::{call_output, Parser};
+use crate::action::{
+    Action, ActionType, AddLiquidity, Protocol, RemoveLiquidity, ReserveSync, RouterSwap, Swap,
+};
+use alloy_sol_types::{sol, SolCall, SolInterface};
+use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
+
+/// The 0.3% swap fee of UniswapV2 pairs and their forks, in hundredths of a basis point.
+pub const UNISWAP_V2_FEE: u32 = 3000;
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface IUniswapV2Pair {
+        function swap(uint amount0Out, uint amount1Out, address to, bytes calldata data) external;
+        function mint(address to) external returns (uint liquidity);
+        function burn(address to) external returns (uint amount0, uint amount1);
+        function sync() external;
+
+        event Swap(
+            address indexed sender,
+            uint amount0In,
+            uint amount1In,
+            uint amount0Out,
+            uint amount1Out,
+            address indexed to
+        );
+        event Mint(address indexed sender, uint amount0, uint amount1);
+        event Burn(address indexed sender, uint amount0, uint amount1, address indexed to);
+        event Sync(uint112 reserve0, uint112 reserve1);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface IUniswapV2Factory {
+        function createPair(address tokenA, address tokenB) external returns (address pair);
+
+        event PairCreated(address indexed token0, address indexed token1, address pair, uint);
+    }
+}
+
+sol! {
+    interface IUniswapV2Callee {
+        function uniswapV2Call(
+            address sender,
+            uint amount0,
+            uint amount1,
+            bytes calldata data
+        ) external;
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq)]
+    interface IUniswapV2Router02 {
+        function swapExactTokensForTokens(
+            uint amountIn,
+            uint amountOutMin,
+            address[] calldata path,
+            address to,
+            uint deadline
+        ) external returns (uint[] memory amounts);
+        function swapTokensForExactTokens(
+            uint amountOut,
+            uint amountInMax,
+            address[] calldata path,
+            address to,
+            uint deadline
+        ) external returns (uint[] memory amounts);
+        function swapExactETHForTokens(
+            uint amountOutMin,
+            address[] calldata path,
+            address to,
+            uint deadline
+        ) external payable returns (uint[] memory amounts);
+        function swapTokensForExactETH(
+            uint amountOut,
+            uint amountInMax,
+            address[] calldata path,
+            address to,
+            uint deadline
+        ) external returns (uint[] memory amounts);
+        function swapExactTokensForETH(
+            uint amountIn,
+            uint amountOutMin,
+            address[] calldata path,
+            address to,
+            uint deadline
+        ) external returns (uint[] memory amounts);
+        function swapETHForExactTokens(
+            uint amountOut,
+            address[] calldata path,
+            address to,
+            uint deadline
+        ) external payable returns (uint[] memory amounts);
+        function swapExactTokensForTokensSupportingFeeOnTransferTokens(
+            uint amountIn,
+            uint amountOutMin,
+            address[] calldata path,
+            address to,
+            uint deadline
+        ) external;
+        function swapExactETHForTokensSupportingFeeOnTransferTokens(
+            uint amountOutMin,
+            address[] calldata path,
+            address to,
+            uint deadline
+        ) external payable;
+        function swapExactTokensForETHSupportingFeeOnTransferTokens(
+            uint amountIn,
+            uint amountOutMin,
+            address[] calldata path,
+            address to,
+            uint deadline
+        ) external;
+    }
+}
+
+impl Parser {
+    /// Parses a call to a UniswapV2 pair or one of its forks.
+    pub fn parse_uniswap_v2_pair(
+        &self,
+        curr: &LocalizedTransactionTrace,
+        protocol: Protocol,
+    ) -> Option<Action> {
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let decoded = IUniswapV2Pair::IUniswapV2PairCalls::decode(&call.input, true).ok()?;
+        let output = call_output(curr);
+
+        let ty = match decoded {
+            IUniswapV2Pair::IUniswapV2PairCalls::swap(swap_call) => {
+                // V2 swaps specify the amount out; the amount in is whatever was transferred to
+                // the pair beforehand
+                let zero_for_one = swap_call.amount0Out.is_zero();
+                let amount_out =
+                    if zero_for_one { swap_call.amount1Out } else { swap_call.amount0Out };
+
+                let (index_in, index_out) = if zero_for_one { (0, 1) } else { (1, 0) };
+
+                ActionType::Swap(Swap::new(
+                    protocol,
+                    call.to,
+                    call.from,
+                    swap_call.to,
+                    self.pools.token(&call.to, index_in, false),
+                    self.pools.token(&call.to, index_out, false),
+                    None,
+                    Some(amount_out),
+                ))
+            }
+            IUniswapV2Pair::IUniswapV2PairCalls::mint(mint_call) => {
+                ActionType::AddLiquidity(AddLiquidity {
+                    protocol,
+                    pool: call.to,
+                    recipient: mint_call.to,
+                    amounts: vec![],
+                    limits: false,
+                    liquidity: output.and_then(|output| {
+                        IUniswapV2Pair::mintCall::decode_returns(output, true)
+                            .ok()
+                            .map(|minted| minted.liquidity)
+                    }),
+                })
+            }
+            IUniswapV2Pair::IUniswapV2PairCalls::burn(burn_call) => {
+                ActionType::RemoveLiquidity(RemoveLiquidity {
+                    protocol,
+                    pool: call.to,
+                    recipient: burn_call.to,
+                    amounts: output
+                        .and_then(|output| {
+                            IUniswapV2Pair::burnCall::decode_returns(output, true).ok()
+                        })
+                        .map(|burned| vec![burned.amount0, burned.amount1])
+                        .unwrap_or_default(),
+                    limits: false,
+                    liquidity: None,
+                })
+            }
+            IUniswapV2Pair::IUniswapV2PairCalls::sync(_) => {
+                ActionType::Sync(ReserveSync { protocol, pool: call.to })
+            }
+        };
+
+        Some(Action::new(ty, curr))
+    }
+
+    /// Parses a swap through a UniswapV2 Router02 or one of its forks.
+    pub fn parse_uniswap_v2_router(
+        &self,
+        curr: &LocalizedTransactionTrace,
+        protocol: Protocol,
+    ) -> Option<Action> {
+        use IUniswapV2Router02::IUniswapV2Router02Calls as Calls;
+
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let decoded = Calls::decode(&call.input, true).ok()?;
+        let output = call_output(curr);
+
+        let (path, recipient, amount_in, amount_out) = match decoded {
+            Calls::swapExactTokensForTokens(swap_call) => {
+                (swap_call.path, swap_call.to, swap_call.amountIn, swap_call.amountOutMin)
+            }
+            Calls::swapTokensForExactTokens(swap_call) => {
+                (swap_call.path, swap_call.to, swap_call.amountInMax, swap_call.amountOut)
+            }
+            Calls::swapExactETHForTokens(swap_call) => {
+                (swap_call.path, swap_call.to, call.value, swap_call.amountOutMin)
+            }
+            Calls::swapTokensForExactETH(swap_call) => {
+                (swap_call.path, swap_call.to, swap_call.amountInMax, swap_call.amountOut)
+            }
+            Calls::swapExactTokensForETH(swap_call) => {
+                (swap_call.path, swap_call.to, swap_call.amountIn, swap_call.amountOutMin)
+            }
+            Calls::swapETHForExactTokens(swap_call) => {
+                (swap_call.path, swap_call.to, call.value, swap_call.amountOut)
+            }
+            Calls::swapExactTokensForTokensSupportingFeeOnTransferTokens(swap_call) => {
+                (swap_call.path, swap_call.to, swap_call.amountIn, swap_call.amountOutMin)
+            }
+            Calls::swapExactETHForTokensSupportingFeeOnTransferTokens(swap_call) => {
+                (swap_call.path, swap_call.to, call.value, swap_call.amountOutMin)
+            }
+            Calls::swapExactTokensForETHSupportingFeeOnTransferTokens(swap_call) => {
+                (swap_call.path, swap_call.to, swap_call.amountIn, swap_call.amountOutMin)
+            }
+        };
+
+        // Every variant returns the same `uint[]`, fee-on-transfer variants return nothing
+        let amounts = output
+            .and_then(|output| {
+                IUniswapV2Router02::swapExactTokensForTokensCall::decode_returns(output, true).ok()
+            })
+            .map(|returned| returned.amounts);
+
+        Some(Action::new(
+            ActionType::RouterSwap(RouterSwap {
+                protocol,
+                router: call.to,
+                path,
+                recipient,
+                amount_in,
+                amount_out,
+                amounts,
+            }),
+            curr,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        classifier::ContractKind,
+        test_utils::{call_trace, parser, pool, returning, word},
+    };
+    use alloy_primitives::{Address, U256};
+    use reth_primitives::H160;
+
+    const PAIR: H160 = H160([1; 20]);
+    const ROUTER: H160 = H160([2; 20]);
+    const TRADER: H160 = H160([3; 20]);
+    const TOKEN_A: H160 = H160([4; 20]);
+    const TOKEN_B: H160 = H160([5; 20]);
+
+    fn parse(trace: LocalizedTransactionTrace) -> ActionType {
+        let parser = parser(
+            vec![],
+            &[
+                (PAIR, ContractKind::Pool(Protocol::Sushiswap)),
+                (ROUTER, ContractKind::Router(Protocol::Sushiswap)),
+            ],
+            &[(PAIR, pool(Protocol::Sushiswap, &[TOKEN_A, TOKEN_B]))],
+        );
+        parser.classify(&trace).ty
+    }
+
+    #[test]
+    fn parses_pair_swaps() {
+        let input = IUniswapV2Pair::swapCall {
+            amount0Out: U256::ZERO,
+            amount1Out: U256::from(990),
+            to: Address::from(TRADER.0),
+            data: vec![],
+        }
+        .encode();
+
+        let ActionType::Swap(swap) = parse(call_trace(ROUTER, PAIR, input)) else {
+            panic!("expected a swap")
+        };
+        assert_eq!(swap.protocol, Protocol::Sushiswap);
+        assert_eq!(
+            (swap.pool, swap.token_in, swap.token_out),
+            (PAIR, Some(TOKEN_A), Some(TOKEN_B))
+        );
+        assert_eq!(swap.recipient, Address::from(TRADER.0));
+        assert_eq!(swap.sender, ROUTER);
+        assert_eq!((swap.amount_in, swap.amount_out), (None, Some(U256::from(990))));
+    }
+
+    #[test]
+    fn parses_pair_burns() {
+        let input = IUniswapV2Pair::burnCall { to: Address::from(TRADER.0) }.encode();
+        let burn = returning(call_trace(TRADER, PAIR, input), [word(10), word(20)].concat());
+
+        let ActionType::RemoveLiquidity(burn) = parse(burn) else {
+            panic!("expected a liquidity removal")
+        };
+        assert_eq!(burn.pool, PAIR);
+        assert_eq!(burn.amounts, [U256::from(10), U256::from(20)]);
+    }
+
+    #[test]
+    fn parses_router_swaps() {
+        let input = IUniswapV2Router02::swapExactTokensForTokensCall {
+            amountIn: U256::from(1000),
+            amountOutMin: U256::from(980),
+            path: vec![Address::from(TOKEN_A.0), Address::from(TOKEN_B.0)],
+            to: Address::from(TRADER.0),
+            deadline: U256::MAX,
+        }
+        .encode();
+        let output = [word(0x20), word(2), word(1000), word(990)].concat();
+
+        let ActionType::RouterSwap(swap) =
+            parse(returning(call_trace(TRADER, ROUTER, input), output))
+        else {
+            panic!("expected a router swap")
+        };
+        assert_eq!(swap.router, ROUTER);
+        assert_eq!(swap.path, [Address::from(TOKEN_A.0), Address::from(TOKEN_B.0)]);
+        assert_eq!((swap.amount_in, swap.amount_out), (U256::from(1000), U256::from(980)));
+        assert_eq!(swap.amounts, Some(vec![U256::from(1000), U256::from(990)]));
+    }
+}
@@ -0,0 +1,233 @@
+use super::{call_output, IUniswapV3FlashCallback, IUniswapV3MintCallback, IUniswapV3Pool, Parser};
+use crate::{
+    action::{Action, ActionType, Burn, Collect, FlashLoan, Mint, Protocol},
+    tree::ActionNode,
+};
+use alloy_primitives::U256;
+use alloy_sol_types::{SolCall, SolInterface};
+use reth_rpc_types::trace::parity::{Action as RethAction, LocalizedTransactionTrace};
+
+impl Parser {
+    /// Parses a `mint`, `burn`, `collect` or `flash` on a Uniswap V3 pool or a fork of it. Flash
+    /// loan fees are only known to the callback and are filled in by [`complete_from_callbacks`].
+    pub fn parse_uniswap_v3_liquidity(
+        &self,
+        curr: &LocalizedTransactionTrace,
+        protocol: Protocol,
+    ) -> Option<Action> {
+        use IUniswapV3Pool::IUniswapV3PoolCalls as Calls;
+
+        let RethAction::Call(call) = &curr.trace.action else { return None };
+
+        let decoded = Calls::decode(&call.input, true).ok()?;
+        let output = call_output(curr);
+
+        let ty = match decoded {
+            Calls::mint(mint_call) => {
+                let paid = output
+                    .and_then(|output| IUniswapV3Pool::mintCall::decode_returns(output, true).ok());
+
+                ActionType::Mint(Mint {
+                    protocol,
+                    pool: call.to,
+                    owner: mint_call.recipient,
+                    tick_lower: mint_call.tickLower,
+                    tick_upper: mint_call.tickUpper,
+                    liquidity: mint_call.amount,
+                    amount_0: paid.as_ref().map(|paid| paid.amount0),
+                    amount_1: paid.map(|paid| paid.amount1),
+                })
+            }
+            Calls::burn(burn_call) => {
+                let credited = output
+                    .and_then(|output| IUniswapV3Pool::burnCall::decode_returns(output, true).ok());
+
+                ActionType::Burn(Burn {
+                    protocol,
+                    pool: call.to,
+                    owner: call.from,
+                    tick_lower: burn_call.tickLower,
+                    tick_upper: burn_call.tickUpper,
+                    liquidity: burn_call.amount,
+                    amount_0: credited.as_ref().map(|credited| credited.amount0),
+                    amount_1: credited.map(|credited| credited.amount1),
+                })
+            }
+            Calls::collect(collect_call) => {
+                let (amount_0, amount_1) = output
+                    .and_then(|output| {
+                        IUniswapV3Pool::collectCall::decode_returns(output, true).ok()
+                    })
+                    .map_or(
+                        (collect_call.amount0Requested, collect_call.amount1Requested),
+                        |collected| (collected.amount0, collected.amount1),
+                    );
+
+                ActionType::Collect(Collect {
+                    protocol,
+                    pool: call.to,
+                    owner: call.from,
+                    recipient: collect_call.recipient,
+                    tick_lower: collect_call.tickLower,
+                    tick_upper: collect_call.tickUpper,
+                    amount_0: U256::from(amount_0),
+                    amount_1: U256::from(amount_1),
+                })
+            }
+            Calls::flash(flash_call) => ActionType::FlashLoan(FlashLoan {
+                protocol,
+                lender: call.to,
+                recipient: flash_call.recipient,
+                tokens: self
+                    .pools
+                    .get(&call.to)
+                    .map(|pool| pool.tokens.clone())
+                    .unwrap_or_default(),
+                amounts: vec![flash_call.amount0, flash_call.amount1],
+                fees: vec![],
+            }),
+            // Swaps are handled by `parse_swap`
+            _ => return None,
+        };
+
+        Some(Action::new(ty, curr))
+    }
+}
+
+/// Fills in what a Uniswap V3 style action only learns through the callback it makes to the
+/// caller: the fees of a `flash`, and the amounts owed for a `mint` whose return data is
+/// missing.
+pub(super) fn complete_from_callbacks(action: &mut Action, children: &[ActionNode]) {
+    for child in children {
+        let ActionType::Unclassified(trace) = &child.action.ty else { continue };
+        let RethAction::Call(call) = &trace.trace.action else { continue };
+
+        match &mut action.ty {
+            ActionType::FlashLoan(flash) if flash.fees.is_empty() => {
+                let Ok(callback) =
+                    IUniswapV3FlashCallback::uniswapV3FlashCallbackCall::decode(&call.input, true)
+                else {
+                    continue
+                };
+
+                flash.fees = vec![callback.fee0, callback.fee1];
+                return
+            }
+            ActionType::Mint(mint) if mint.amount_0.is_none() => {
+                let Ok(callback) =
+                    IUniswapV3MintCallback::uniswapV3MintCallbackCall::decode(&call.input, true)
+                else {
+                    continue
+                };
+
+                mint.amount_0 = Some(callback.amount0Owed);
+                mint.amount_1 = Some(callback.amount1Owed);
+                return
+            }
+            _ => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        classifier::ContractKind,
+        test_utils::{at, call_trace, pool, returning, reverted, word},
+    };
+    use alloy_primitives::Address;
+    use reth_primitives::H160;
+
+    const POOL: H160 = H160([1; 20]);
+    const LP: H160 = H160([2; 20]);
+    const TOKEN_A: H160 = H160([3; 20]);
+    const TOKEN_B: H160 = H160([4; 20]);
+
+    fn parser(block_trace: Vec<LocalizedTransactionTrace>) -> Parser {
+        crate::test_utils::parser(
+            block_trace,
+            &[(POOL, ContractKind::Pool(Protocol::UniswapV3))],
+            &[(POOL, pool(Protocol::UniswapV3, &[TOKEN_A, TOKEN_B]))],
+        )
+    }
+
+    #[test]
+    fn parses_burns_and_collects() {
+        let burn = IUniswapV3Pool::burnCall { tickLower: -600, tickUpper: 600, amount: 1000 };
+        let burn = returning(call_trace(LP, POOL, burn.encode()), [word(10), word(20)].concat());
+
+        let ActionType::Burn(burn) = parser(vec![]).classify(&burn).ty else {
+            panic!("expected a burn")
+        };
+        assert_eq!((burn.owner, burn.tick_lower, burn.tick_upper), (LP, -600, 600));
+        assert_eq!((burn.amount_0, burn.amount_1), (Some(U256::from(10)), Some(U256::from(20))));
+
+        let collect = IUniswapV3Pool::collectCall {
+            recipient: Address::from(LP.0),
+            tickLower: -600,
+            tickUpper: 600,
+            amount0Requested: 10,
+            amount1Requested: u128::MAX,
+        };
+        let collect = reverted(call_trace(LP, POOL, collect.encode()));
+
+        let ActionType::Collect(collect) = parser(vec![]).classify(&collect).ty else {
+            panic!("expected a collect")
+        };
+        assert_eq!((collect.amount_0, collect.amount_1), (U256::from(10), U256::from(u128::MAX)));
+    }
+
+    #[test]
+    fn fills_flash_fees_from_the_callback() {
+        let flash = IUniswapV3Pool::flashCall {
+            recipient: Address::from(LP.0),
+            amount0: U256::from(1000),
+            amount1: U256::ZERO,
+            data: vec![],
+        };
+        let callback = IUniswapV3FlashCallback::uniswapV3FlashCallbackCall {
+            fee0: U256::from(3),
+            fee1: U256::ZERO,
+            data: vec![],
+        };
+        let trees = parser(vec![
+            at(call_trace(LP, POOL, flash.encode()), 0, vec![], 1),
+            at(call_trace(POOL, LP, callback.encode()), 0, vec![0], 0),
+        ])
+        .parse();
+
+        let ActionType::FlashLoan(flash) = &trees[0].root.action.ty else {
+            panic!("expected a flash loan")
+        };
+        assert_eq!(flash.tokens, [TOKEN_A, TOKEN_B]);
+        assert_eq!(flash.amounts, [U256::from(1000), U256::ZERO]);
+        assert_eq!(flash.fees, [U256::from(3), U256::ZERO]);
+    }
+
+    #[test]
+    fn fills_mint_amounts_from_the_callback() {
+        let mint = IUniswapV3Pool::mintCall {
+            recipient: Address::from(LP.0),
+            tickLower: -600,
+            tickUpper: 600,
+            amount: 1000,
+            data: vec![],
+        };
+        let callback = IUniswapV3MintCallback::uniswapV3MintCallbackCall {
+            amount0Owed: U256::from(5),
+            amount1Owed: U256::from(6),
+            data: vec![],
+        };
+        let trees = parser(vec![
+            at(returning(call_trace(LP, POOL, mint.encode()), vec![]), 0, vec![], 1),
+            at(call_trace(POOL, LP, callback.encode()), 0, vec![0], 0),
+        ])
+        .parse();
+
+        let ActionType::Mint(mint) = &trees[0].root.action.ty else { panic!("expected a mint") };
+        assert_eq!(mint.owner, Address::from(LP.0));
+        assert_eq!(mint.liquidity, 1000);
+        assert_eq!((mint.amount_0, mint.amount_1), (Some(U256::from(5)), Some(U256::from(6))));
+    }
+}
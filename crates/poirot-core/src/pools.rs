@@ -0,0 +1,463 @@
+use crate::{
+    abi::parse_address,
+    action::{ActionType, PoolCreation, Protocol},
+    classifier::{ContractClassifier, ContractKind},
+    events::decode_typed,
+    parser::{to_h160, IUniswapV2Factory, IUniswapV3Factory, UNISWAP_V2_FEE},
+    tree::TransactionTree,
+    TracingClient,
+};
+use reth_primitives::{BlockHashOrNumber, H160};
+use reth_provider::ReceiptProvider;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    ops::RangeInclusive,
+    path::Path,
+};
+
+/// What we know about a pool's tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolInfo {
+    pub protocol: Protocol,
+    /// Pool tokens, in the order the pool indexes them (`token0`, `token1`, or Curve `coins`), or
+    /// the underlying token of a Compound market.
+    pub tokens: Vec<H160>,
+    /// Tokens traded by Curve `exchange_underlying`, in the order the pool indexes them.
+    pub underlying_tokens: Vec<H160>,
+    /// Swap fee in hundredths of a basis point, if known.
+    pub fee: Option<u32>,
+    /// Factory that deployed the pool, if known.
+    pub factory: Option<H160>,
+    /// Block the pool was created in, if known.
+    pub created_at: Option<u64>,
+}
+
+/// Well-known mainnet pools every [`PoolRegistry::mainnet`] registry starts with.
+const MAINNET_POOLS: &[(&str, Protocol, &[&str], &[&str])] = &[
+    (
+        // Curve 3pool: DAI, USDC, USDT
+        "0xbebc44782c7db0a1a60cb6fe97d0b483032ff1c7",
+        Protocol::Curve,
+        &[
+            "0x6b175474e89094c44da98b954eedeac495271d0f",
+            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+            "0xdac17f958d2ee523a2206206994597c13d831ec7",
+        ],
+        &[],
+    ),
+    (
+        // Curve tricrypto2: USDT, WBTC, WETH
+        "0xd51a44d3fae010294c616388b506acda1bfaae46",
+        Protocol::Curve,
+        &[
+            "0xdac17f958d2ee523a2206206994597c13d831ec7",
+            "0x2260fac5e5542a773aa44fbcfedf7c193bc2c599",
+            "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+        ],
+        &[],
+    ),
+    (
+        // DODO V1 WETH/USDC, base then quote token
+        "0x75c23271661d9d143dcb617222bc4bec783eff34",
+        Protocol::Dodo,
+        &[
+            "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+        ],
+        &[],
+    ),
+    (
+        // DODO V1 USDT/USDC
+        "0xc9f93163c99695c6526b799ebca2207fdf7d61ad",
+        Protocol::Dodo,
+        &[
+            "0xdac17f958d2ee523a2206206994597c13d831ec7",
+            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+        ],
+        &[],
+    ),
+    (
+        // UniswapV2 USDC/WETH
+        "0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc",
+        Protocol::UniswapV2,
+        &[
+            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+            "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+        ],
+        &[],
+    ),
+    (
+        // UniswapV2 WETH/USDT
+        "0x0d4a11d5eeaac28ec3f61d100daf4d40471f1852",
+        Protocol::UniswapV2,
+        &[
+            "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+            "0xdac17f958d2ee523a2206206994597c13d831ec7",
+        ],
+        &[],
+    ),
+    (
+        // Sushiswap USDC/WETH
+        "0x397ff1542f962076d0bfe58ea045ffa2d347aca0",
+        Protocol::Sushiswap,
+        &[
+            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+            "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+        ],
+        &[],
+    ),
+    (
+        // Uniswap V3 USDC/WETH 0.05%
+        "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640",
+        Protocol::UniswapV3,
+        &[
+            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+            "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+        ],
+        &[],
+    ),
+    (
+        // Compound cETH, repaid in ETH and priced as WETH
+        "0x4ddc2d193948926d02f9b1fe9e1daa0718270ed5",
+        Protocol::CompoundV2,
+        &["0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"],
+        &[],
+    ),
+    (
+        // Compound cUSDC
+        "0x39aa39c021dfbae8fac545936693ac917d5e7563",
+        Protocol::CompoundV2,
+        &["0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"],
+        &[],
+    ),
+    (
+        // Compound cDAI
+        "0x5d3a536e4d6dbd6114cc1ead35777bab948e3643",
+        Protocol::CompoundV2,
+        &["0x6b175474e89094c44da98b954eedeac495271d0f"],
+        &[],
+    ),
+    (
+        // Compound V3 cUSDCv3, base asset USDC
+        "0xc3d688b66703497daa19211eedff47f25384cdc3",
+        Protocol::CompoundV3,
+        &["0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"],
+        &[],
+    ),
+];
+
+/// Pool address → [`PoolInfo`] lookup used by the parser to resolve the tokens of a pool.
+#[derive(Debug, Clone, Default)]
+pub struct PoolRegistry {
+    pools: HashMap<H160, PoolInfo>,
+}
+
+impl PoolRegistry {
+    pub fn new() -> Self {
+        Self { pools: HashMap::new() }
+    }
+
+    /// A registry seeded with well-known mainnet pools.
+    pub fn mainnet() -> Self {
+        let parse = |addresses: &[&str]| -> Vec<H160> {
+            addresses.iter().map(|address| parse_address(address).unwrap()).collect()
+        };
+
+        let mut registry = Self::new();
+        for (pool, protocol, tokens, underlying_tokens) in MAINNET_POOLS {
+            registry.insert(
+                parse_address(pool).unwrap(),
+                PoolInfo {
+                    protocol: *protocol,
+                    tokens: parse(tokens),
+                    underlying_tokens: parse(underlying_tokens),
+                    fee: None,
+                    factory: None,
+                    created_at: None,
+                },
+            );
+        }
+        registry
+    }
+
+    /// Loads a registry written by [`PoolRegistry::save`].
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut registry = Self::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+
+            let (pool, info) = parse_pool_line(line)
+                .ok_or_else(|| format!("Invalid pool registry entry {line}"))?;
+            registry.insert(pool, info);
+        }
+
+        Ok(registry)
+    }
+
+    /// Writes the registry with one pool per line:
+    /// `<pool> <Protocol> <tokens> <underlying tokens> <fee> <factory> <creation block>`, where
+    /// token lists are comma separated and unknown values are `-`.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        let format_tokens = |tokens: &[H160]| -> String {
+            if tokens.is_empty() {
+                return "-".to_string()
+            }
+            tokens.iter().map(|token| format!("{token:?}")).collect::<Vec<_>>().join(",")
+        };
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+
+        for (pool, info) in &self.pools {
+            writeln!(
+                writer,
+                "{pool:?} {:?} {} {} {} {} {}",
+                info.protocol,
+                format_tokens(&info.tokens),
+                format_tokens(&info.underlying_tokens),
+                optional(info.fee.map(|fee| fee.to_string())),
+                optional(info.factory.map(|factory| format!("{factory:?}"))),
+                optional(info.created_at.map(|block| block.to_string())),
+            )?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn insert(&mut self, pool: H160, info: PoolInfo) {
+        self.pools.insert(pool, info);
+    }
+
+    /// Registers the pool deployed by `creation` in `block`. Returns `false` if the creation
+    /// reverted.
+    pub fn index_creation(&mut self, creation: &PoolCreation, block: u64) -> bool {
+        let Some(pool) = creation.pool else { return false };
+
+        self.insert(
+            pool,
+            PoolInfo {
+                protocol: creation.protocol,
+                tokens: vec![to_h160(&creation.token_0), to_h160(&creation.token_1)],
+                underlying_tokens: vec![],
+                fee: Some(creation.fee),
+                factory: Some(creation.factory),
+                created_at: Some(block),
+            },
+        );
+        true
+    }
+
+    /// Registers every pool created in the parsed transactions. Returns the number of pools
+    /// added.
+    pub fn index_trees(&mut self, trees: &[TransactionTree]) -> usize {
+        let mut added = 0;
+        for action in trees.iter().flat_map(TransactionTree::actions) {
+            if let ActionType::PoolCreation(creation) = &action.ty {
+                added += usize::from(self.index_creation(creation, action.block));
+            }
+        }
+        added
+    }
+
+    /// Registers the pools created in `blocks` by the factories classified in `contracts`,
+    /// reading their `PairCreated`/`PoolCreated` logs from the receipts stored in the database.
+    /// Returns the number of pools added.
+    pub fn bootstrap(
+        &mut self,
+        tracer: &TracingClient,
+        contracts: &ContractClassifier,
+        blocks: RangeInclusive<u64>,
+    ) -> eyre::Result<usize> {
+        let mut added = 0;
+
+        for block in blocks {
+            let receipts = tracer.provider.receipts_by_block(BlockHashOrNumber::Number(block))?;
+            let Some(receipts) = receipts else { continue };
+
+            for log in receipts.iter().flat_map(|receipt| &receipt.logs) {
+                let Some(ContractKind::Factory(protocol)) = contracts.get(&log.address) else {
+                    continue
+                };
+
+                let creation = if let Some(created) =
+                    decode_typed::<IUniswapV3Factory::PoolCreated>(log)
+                {
+                    PoolCreation::new(
+                        protocol,
+                        log.address,
+                        Some(to_h160(&created.pool)),
+                        created.token0,
+                        created.token1,
+                        created.fee,
+                    )
+                } else if let Some(created) = decode_typed::<IUniswapV2Factory::PairCreated>(log) {
+                    PoolCreation::new(
+                        protocol,
+                        log.address,
+                        Some(to_h160(&created.pair)),
+                        created.token0,
+                        created.token1,
+                        UNISWAP_V2_FEE,
+                    )
+                } else {
+                    continue
+                };
+
+                added += usize::from(self.index_creation(&creation, block));
+            }
+        }
+
+        Ok(added)
+    }
+
+    pub fn get(&self, pool: &H160) -> Option<&PoolInfo> {
+        self.pools.get(pool)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&H160, &PoolInfo)> {
+        self.pools.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pools.is_empty()
+    }
+
+    /// Returns the token at `index` in the pool, or in its underlying tokens if `underlying`.
+    pub fn token(&self, pool: &H160, index: usize, underlying: bool) -> Option<H160> {
+        let info = self.get(pool)?;
+        let tokens = if underlying { &info.underlying_tokens } else { &info.tokens };
+        tokens.get(index).copied()
+    }
+}
+
+/// Parses a line written by [`PoolRegistry::save`].
+fn parse_pool_line(line: &str) -> Option<(H160, PoolInfo)> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let [pool, protocol, tokens, underlying_tokens, fee, factory, created_at] = fields[..] else {
+        return None
+    };
+
+    let parse_tokens = |tokens: &str| -> Option<Vec<H160>> {
+        if tokens == "-" {
+            return Some(vec![])
+        }
+        tokens.split(',').map(parse_address).collect()
+    };
+    let optional = |value: &str| (value != "-").then_some(value);
+
+    let info = PoolInfo {
+        protocol: protocol.parse().ok()?,
+        tokens: parse_tokens(tokens)?,
+        underlying_tokens: parse_tokens(underlying_tokens)?,
+        fee: optional(fee).map(str::parse::<u32>).transpose().ok()?,
+        factory: match optional(factory) {
+            Some(factory) => Some(parse_address(factory)?),
+            None => None,
+        },
+        created_at: optional(created_at).map(str::parse::<u64>).transpose().ok()?,
+    };
+
+    Some((parse_address(pool)?, info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::pool;
+    use alloy_primitives::Address;
+
+    const POOL: H160 = H160([1; 20]);
+    const CURVE_POOL: H160 = H160([2; 20]);
+    const TOKEN_A: H160 = H160([3; 20]);
+    const TOKEN_B: H160 = H160([4; 20]);
+    const FACTORY: H160 = H160([5; 20]);
+
+    fn uniswap_v3_pool() -> PoolInfo {
+        PoolInfo {
+            fee: Some(500),
+            factory: Some(FACTORY),
+            created_at: Some(12_370_624),
+            ..pool(Protocol::UniswapV3, &[TOKEN_A, TOKEN_B])
+        }
+    }
+
+    fn curve_pool() -> PoolInfo {
+        PoolInfo {
+            underlying_tokens: vec![TOKEN_B, TOKEN_A],
+            ..pool(Protocol::Curve, &[TOKEN_A, TOKEN_B])
+        }
+    }
+
+    #[test]
+    fn parses_pool_lines() {
+        let line = format!("{POOL:?} UniswapV3 {TOKEN_A:?},{TOKEN_B:?} - 500 {FACTORY:?} 12370624");
+        assert_eq!(parse_pool_line(&line), Some((POOL, uniswap_v3_pool())));
+
+        let line =
+            format!("{CURVE_POOL:?} Curve {TOKEN_A:?},{TOKEN_B:?} {TOKEN_B:?},{TOKEN_A:?} - - -");
+        assert_eq!(parse_pool_line(&line), Some((CURVE_POOL, curve_pool())));
+    }
+
+    #[test]
+    fn rejects_invalid_pool_lines() {
+        let valid = format!("{POOL:?} UniswapV3 {TOKEN_A:?} - 500 - -");
+        assert!(parse_pool_line(&valid).is_some());
+
+        for line in [
+            format!("{POOL:?} UniswapV3 {TOKEN_A:?} - 500 -"),
+            format!("{POOL:?} NotAProtocol {TOKEN_A:?} - 500 - -"),
+            format!("{POOL:?} UniswapV3 {TOKEN_A:?},0x01 - 500 - -"),
+            format!("{POOL:?} UniswapV3 {TOKEN_A:?} - 0.05 - -"),
+            format!("{POOL:?} UniswapV3 {TOKEN_A:?} - 500 - latest"),
+        ] {
+            assert_eq!(parse_pool_line(&line), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn saves_and_loads_registries() {
+        let mut registry = PoolRegistry::new();
+        registry.insert(POOL, uniswap_v3_pool());
+        registry.insert(CURVE_POOL, curve_pool());
+
+        let path = std::env::temp_dir().join(format!("pool-registry-{}.txt", std::process::id()));
+        registry.save(&path).unwrap();
+        let loaded = PoolRegistry::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.get(&POOL), Some(&uniswap_v3_pool()));
+        assert_eq!(loaded.get(&CURVE_POOL), Some(&curve_pool()));
+    }
+
+    #[test]
+    fn indexes_created_pools() {
+        let (token_a, token_b) = (Address::from(TOKEN_A.0), Address::from(TOKEN_B.0));
+        let mut registry = PoolRegistry::new();
+
+        let reverted = PoolCreation::new(Protocol::UniswapV3, FACTORY, None, token_a, token_b, 500);
+        assert!(!registry.index_creation(&reverted, 12_370_624));
+        assert!(registry.is_empty());
+
+        let created =
+            PoolCreation::new(Protocol::UniswapV3, FACTORY, Some(POOL), token_a, token_b, 500);
+        assert!(registry.index_creation(&created, 12_370_624));
+        assert_eq!(registry.get(&POOL), Some(&uniswap_v3_pool()));
+        assert_eq!(registry.token(&POOL, 1, false), Some(TOKEN_B));
+        assert_eq!(registry.token(&POOL, 0, true), None);
+    }
+}
@@ -0,0 +1,508 @@
+use crate::{action::Protocol, parser::ICErc20, pools::PoolRegistry, TracingClient};
+use alloy_primitives::U256;
+use alloy_sol_types::{sol, SolCall};
+use reth_primitives::H160;
+use std::collections::{HashMap, HashSet};
+
+sol! {
+    interface IUniswapV2Reserves {
+        function getReserves()
+            external
+            view
+            returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast);
+    }
+}
+
+sol! {
+    interface IUniswapV3PoolState {
+        function slot0()
+            external
+            view
+            returns (
+                uint160 sqrtPriceX96,
+                int24 tick,
+                uint16 observationIndex,
+                uint16 observationCardinality,
+                uint16 observationCardinalityNext,
+                uint8 feeProtocol,
+                bool unlocked
+            );
+        function liquidity() external view returns (uint128);
+    }
+}
+
+/// Longest pool path searched from a token to the quote token.
+pub const MAX_HOPS: usize = 3;
+
+/// Most paths of the shortest length priced before picking the deepest one.
+const MAX_PATHS: usize = 16;
+
+/// A token price read from pool state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenPrice {
+    pub token: H160,
+    /// Raw units of the quote token per raw unit of `token`.
+    pub price: f64,
+    /// The pools, and Compound market, the price was derived through, from `token` to the quote
+    /// token.
+    pub path: Vec<H160>,
+}
+
+/// Reserves of a two-token pool, virtual ones for concentrated liquidity pools.
+#[derive(Debug, Clone, Copy)]
+struct PoolState {
+    token_0: H160,
+    reserve_0: f64,
+    reserve_1: f64,
+}
+
+/// Prices tokens in a quote token (e.g. WETH or USDC) from the state of UniswapV2 and Uniswap V3
+/// style pools at a block. Pools of the [`PoolRegistry`] form a token graph, and a token is
+/// priced along the shortest paths to the quote token, picking the path whose shallowest pool is
+/// the deepest. Compound V2 cTokens are priced as their underlying token at the market's exchange
+/// rate. Pool states and prices are cached for the block last resolved.
+#[derive(Debug, Clone)]
+pub struct OnChainPricer {
+    quote: H160,
+    /// Protocol and `token0` of every priceable pool.
+    pools: HashMap<H160, (Protocol, H160)>,
+    /// Sorted token pair → pools trading it.
+    pairs: HashMap<(H160, H160), Vec<H160>>,
+    neighbours: HashMap<H160, HashSet<H160>>,
+    /// Compound V2 cToken → underlying token.
+    markets: HashMap<H160, H160>,
+    block: Option<u64>,
+    states: HashMap<H160, Option<PoolState>>,
+    prices: HashMap<H160, Option<TokenPrice>>,
+}
+
+impl OnChainPricer {
+    pub fn new(registry: &PoolRegistry, quote: H160) -> Self {
+        let mut pricer = Self {
+            quote,
+            pools: HashMap::new(),
+            pairs: HashMap::new(),
+            neighbours: HashMap::new(),
+            markets: HashMap::new(),
+            block: None,
+            states: HashMap::new(),
+            prices: HashMap::new(),
+        };
+
+        for (pool, info) in registry.iter() {
+            if let (Protocol::CompoundV2, [underlying]) = (info.protocol, &info.tokens[..]) {
+                pricer.markets.insert(*pool, *underlying);
+                continue
+            }
+
+            let priceable = matches!(
+                info.protocol,
+                Protocol::UniswapV2 |
+                    Protocol::Sushiswap |
+                    Protocol::UniswapV3 |
+                    Protocol::SushiswapV3
+            );
+            let [token_0, token_1] = info.tokens[..] else { continue };
+            if !priceable {
+                continue
+            }
+
+            pricer.pools.insert(*pool, (info.protocol, token_0));
+            pricer.pairs.entry(sorted(token_0, token_1)).or_default().push(*pool);
+            pricer.neighbours.entry(token_0).or_default().insert(token_1);
+            pricer.neighbours.entry(token_1).or_default().insert(token_0);
+        }
+
+        pricer
+    }
+
+    /// Returns the price of `token` at the end of `block`, reading the state of the pools on its
+    /// candidate paths. `None` if no path of at most [`MAX_HOPS`] pools with liquidity leads to
+    /// the quote token.
+    pub async fn resolve(
+        &mut self,
+        tracer: &TracingClient,
+        token: H160,
+        block: u64,
+    ) -> Option<&TokenPrice> {
+        if self.block != Some(block) {
+            self.block = Some(block);
+            self.states.clear();
+            self.prices.clear();
+        }
+
+        if !self.prices.contains_key(&token) {
+            let price = self.price_paths(tracer, token, block).await;
+            self.prices.insert(token, price);
+        }
+
+        self.prices[&token].as_ref()
+    }
+
+    /// The prices resolved for `block`, a [`PriceSource`](crate::inspectors::PriceSource) for
+    /// the block's actions. Empty unless `block` is the last block resolved.
+    pub fn block_prices(&self, block: u64) -> HashMap<H160, f64> {
+        if self.block != Some(block) {
+            return HashMap::new()
+        }
+
+        self.prices
+            .iter()
+            .filter_map(|(token, price)| Some((*token, price.as_ref()?.price)))
+            .collect()
+    }
+
+    async fn price_paths(
+        &mut self,
+        tracer: &TracingClient,
+        token: H160,
+        block: u64,
+    ) -> Option<TokenPrice> {
+        let Some(underlying) = self.markets.get(&token).copied() else {
+            return self.price_token(tracer, token, block).await
+        };
+
+        let output = tracer
+            .eth_call(token, ICErc20::exchangeRateStoredCall {}.encode(), block)
+            .await
+            .ok()?;
+        let underlying = self.price_token(tracer, underlying, block).await?;
+        ctoken_price(token, &output, underlying)
+    }
+
+    async fn price_token(
+        &mut self,
+        tracer: &TracingClient,
+        token: H160,
+        block: u64,
+    ) -> Option<TokenPrice> {
+        if token == self.quote {
+            return Some(TokenPrice { token, price: 1.0, path: vec![] })
+        }
+
+        let mut best: Option<(f64, TokenPrice)> = None;
+        for tokens in self.token_paths(token) {
+            let Some((depth, price)) = self.price_path(tracer, &tokens, block).await else {
+                continue
+            };
+
+            if best.as_ref().map_or(true, |(best_depth, _)| depth > *best_depth) {
+                best = Some((depth, price));
+            }
+        }
+
+        best.map(|(_, price)| price)
+    }
+
+    /// Prices the first token of `tokens` through the deepest pool of each hop, walking back
+    /// from the quote token. Also returns the depth of the path: the smallest reserve, valued in
+    /// the quote token, of the token each hop buys.
+    async fn price_path(
+        &mut self,
+        tracer: &TracingClient,
+        tokens: &[H160],
+        block: u64,
+    ) -> Option<(f64, TokenPrice)> {
+        let mut price = 1.0;
+        let mut depth = f64::INFINITY;
+        let mut path = vec![];
+
+        for hop in tokens.windows(2).rev() {
+            let (sold, bought) = (hop[0], hop[1]);
+            let (pool, reserve_sold, reserve_bought) =
+                self.deepest_pool(tracer, sold, bought, block).await?;
+
+            // `price` is the price of `bought` so far
+            depth = depth.min(reserve_bought * price);
+            price *= reserve_bought / reserve_sold;
+            path.push(pool);
+        }
+
+        path.reverse();
+        Some((depth, TokenPrice { token: tokens[0], price, path }))
+    }
+
+    /// The pool trading `sold` for `bought` with the most `bought` in reserve, with its reserves
+    /// of both tokens.
+    async fn deepest_pool(
+        &mut self,
+        tracer: &TracingClient,
+        sold: H160,
+        bought: H160,
+        block: u64,
+    ) -> Option<(H160, f64, f64)> {
+        let pools = self.pairs.get(&sorted(sold, bought))?.clone();
+
+        let mut deepest: Option<(H160, f64, f64)> = None;
+        for pool in pools {
+            let Some(state) = self.pool_state(tracer, pool, block).await else { continue };
+            let (reserve_sold, reserve_bought) = if state.token_0 == sold {
+                (state.reserve_0, state.reserve_1)
+            } else {
+                (state.reserve_1, state.reserve_0)
+            };
+
+            if deepest.map_or(true, |(_, _, deepest_bought)| reserve_bought > deepest_bought) {
+                deepest = Some((pool, reserve_sold, reserve_bought));
+            }
+        }
+
+        deepest
+    }
+
+    async fn pool_state(
+        &mut self,
+        tracer: &TracingClient,
+        pool: H160,
+        block: u64,
+    ) -> Option<PoolState> {
+        if !self.states.contains_key(&pool) {
+            let state = self.fetch_pool_state(tracer, pool, block).await;
+            self.states.insert(pool, state);
+        }
+
+        self.states[&pool]
+    }
+
+    /// Reads `getReserves` of a UniswapV2 style pair, or `slot0` and `liquidity` of a Uniswap V3
+    /// style pool. Pools that can't be read, e.g. because they don't exist yet, or are empty
+    /// aren't used.
+    async fn fetch_pool_state(
+        &self,
+        tracer: &TracingClient,
+        pool: H160,
+        block: u64,
+    ) -> Option<PoolState> {
+        let (protocol, token_0) = self.pools[&pool];
+
+        let (reserve_0, reserve_1) = match protocol {
+            Protocol::UniswapV3 | Protocol::SushiswapV3 => {
+                let output = tracer
+                    .eth_call(pool, IUniswapV3PoolState::slot0Call {}.encode(), block)
+                    .await
+                    .ok()?;
+                let slot0 = IUniswapV3PoolState::slot0Call::decode_returns(&output, true).ok()?;
+                let output = tracer
+                    .eth_call(pool, IUniswapV3PoolState::liquidityCall {}.encode(), block)
+                    .await
+                    .ok()?;
+                let liquidity =
+                    IUniswapV3PoolState::liquidityCall::decode_returns(&output, true).ok()?._0;
+
+                // Virtual reserves at the current price: x = L / √P and y = L·√P
+                let sqrt_price = u256_to_f64(U256::from(slot0.sqrtPriceX96)) / 2f64.powi(96);
+                (liquidity as f64 / sqrt_price, liquidity as f64 * sqrt_price)
+            }
+            _ => {
+                let output = tracer
+                    .eth_call(pool, IUniswapV2Reserves::getReservesCall {}.encode(), block)
+                    .await
+                    .ok()?;
+                let reserves =
+                    IUniswapV2Reserves::getReservesCall::decode_returns(&output, true).ok()?;
+                (
+                    u256_to_f64(U256::from(reserves.reserve0)),
+                    u256_to_f64(U256::from(reserves.reserve1)),
+                )
+            }
+        };
+
+        let usable = |reserve: f64| reserve.is_finite() && reserve > 0.0;
+        (usable(reserve_0) && usable(reserve_1)).then_some(PoolState {
+            token_0,
+            reserve_0,
+            reserve_1,
+        })
+    }
+
+    /// The shortest token paths from `token` to the quote token, at most [`MAX_PATHS`] of them.
+    fn token_paths(&self, token: H160) -> Vec<Vec<H160>> {
+        // Token → the tokens of the previous layer it is reached from
+        let mut parents = HashMap::from([(token, vec![])]);
+        let mut layer = vec![token];
+
+        for _ in 0..MAX_HOPS {
+            let mut next = HashMap::<H160, Vec<H160>>::new();
+            for from in &layer {
+                for to in self.neighbours.get(from).into_iter().flatten() {
+                    if !parents.contains_key(to) {
+                        next.entry(*to).or_default().push(*from);
+                    }
+                }
+            }
+
+            if next.is_empty() {
+                break
+            }
+            layer = next.keys().copied().collect();
+            parents.extend(next);
+
+            if parents.contains_key(&self.quote) {
+                return expand_paths(&parents, token, self.quote)
+            }
+        }
+
+        vec![]
+    }
+}
+
+/// Walks the BFS `parents` back from `quote` to `token`.
+fn expand_paths(parents: &HashMap<H160, Vec<H160>>, token: H160, quote: H160) -> Vec<Vec<H160>> {
+    let mut paths = vec![vec![quote]];
+    while paths.first().and_then(|path| path.last()) != Some(&token) {
+        paths = paths
+            .into_iter()
+            .flat_map(|path| {
+                parents[path.last().unwrap()].iter().map(move |parent| {
+                    let mut path = path.clone();
+                    path.push(*parent);
+                    path
+                })
+            })
+            .take(MAX_PATHS)
+            .collect();
+    }
+
+    paths.iter_mut().for_each(|path| path.reverse());
+    paths
+}
+
+fn sorted(a: H160, b: H160) -> (H160, H160) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Converts an amount to a float, losing precision beyond 53 bits.
+pub fn u256_to_f64(amount: U256) -> f64 {
+    amount.to_string().parse().unwrap_or(f64::MAX)
+}
+
+/// Prices the Compound V2 `ctoken` as its `underlying` token at the exchange rate returned by
+/// the market's `exchangeRateStored`.
+fn ctoken_price(ctoken: H160, exchange_rate: &[u8], underlying: TokenPrice) -> Option<TokenPrice> {
+    // The rate is the raw underlying per raw cToken, scaled by 1e18
+    let rate = ICErc20::exchangeRateStoredCall::decode_returns(exchange_rate, true).ok()?._0;
+    let rate = u256_to_f64(rate) / 1e18;
+
+    let mut path = vec![ctoken];
+    path.extend(underlying.path);
+    Some(TokenPrice { token: ctoken, price: underlying.price * rate, path })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{pool, word};
+
+    const QUOTE: H160 = H160([1; 20]);
+    const TOKEN: H160 = H160([2; 20]);
+    const USDC: H160 = H160([3; 20]);
+    const DAI: H160 = H160([4; 20]);
+    const USDT: H160 = H160([5; 20]);
+
+    fn pricer(pools: &[(Protocol, &[H160])]) -> OnChainPricer {
+        let mut registry = PoolRegistry::new();
+        for (index, (protocol, tokens)) in pools.iter().enumerate() {
+            registry.insert(H160::from_low_u64_be(index as u64 + 0x100), pool(*protocol, tokens));
+        }
+        OnChainPricer::new(&registry, QUOTE)
+    }
+
+    fn paths(pricer: &OnChainPricer, token: H160) -> Vec<Vec<H160>> {
+        let mut paths = pricer.token_paths(token);
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn finds_only_the_shortest_paths() {
+        let pricer = pricer(&[
+            (Protocol::UniswapV2, &[TOKEN, QUOTE]),
+            (Protocol::UniswapV3, &[TOKEN, USDC]),
+            (Protocol::Sushiswap, &[USDC, QUOTE]),
+        ]);
+
+        assert_eq!(paths(&pricer, TOKEN), vec![vec![TOKEN, QUOTE]]);
+    }
+
+    #[test]
+    fn finds_every_path_through_the_last_layer() {
+        let pricer = pricer(&[
+            (Protocol::UniswapV2, &[TOKEN, USDC]),
+            (Protocol::UniswapV2, &[TOKEN, DAI]),
+            (Protocol::UniswapV3, &[USDC, QUOTE]),
+            (Protocol::SushiswapV3, &[QUOTE, DAI]),
+            // A second pool for the same pair adds no path
+            (Protocol::Sushiswap, &[USDC, QUOTE]),
+        ]);
+
+        assert_eq!(paths(&pricer, TOKEN), vec![vec![TOKEN, USDC, QUOTE], vec![TOKEN, DAI, QUOTE]]);
+    }
+
+    #[test]
+    fn searches_at_most_max_hops() {
+        let pricer = pricer(&[
+            (Protocol::UniswapV2, &[TOKEN, USDC]),
+            (Protocol::UniswapV2, &[USDC, DAI]),
+            (Protocol::UniswapV2, &[DAI, USDT]),
+            (Protocol::UniswapV2, &[USDT, QUOTE]),
+        ]);
+
+        assert!(paths(&pricer, TOKEN).is_empty());
+        assert_eq!(paths(&pricer, USDC), vec![vec![USDC, DAI, USDT, QUOTE]]);
+    }
+
+    #[test]
+    fn ignores_pools_it_cant_price() {
+        let pricer = pricer(&[
+            (Protocol::Curve, &[TOKEN, USDC, QUOTE]),
+            (Protocol::Balancer, &[TOKEN, QUOTE]),
+            (Protocol::CompoundV2, &[QUOTE]),
+        ]);
+
+        assert!(paths(&pricer, TOKEN).is_empty());
+        assert!(pricer.neighbours.is_empty());
+    }
+
+    #[test]
+    fn indexes_compound_markets() {
+        let pricer =
+            pricer(&[(Protocol::CompoundV2, &[USDC]), (Protocol::UniswapV2, &[USDC, QUOTE])]);
+
+        assert_eq!(pricer.markets, HashMap::from([(H160::from_low_u64_be(0x100), USDC)]));
+        assert_eq!(paths(&pricer, USDC), vec![vec![USDC, QUOTE]]);
+    }
+
+    #[test]
+    fn prices_ctokens_at_the_exchange_rate() {
+        let ctoken = H160::from_low_u64_be(0x100);
+        let underlying = TokenPrice { token: USDC, price: 2.0, path: vec![H160([9; 20])] };
+
+        let price = ctoken_price(ctoken, &word(5e17 as i128), underlying.clone()).unwrap();
+        assert_eq!(price.token, ctoken);
+        assert_eq!(price.price, 1.0);
+        assert_eq!(price.path, vec![ctoken, H160([9; 20])]);
+
+        assert!(ctoken_price(ctoken, &[], underlying).is_none());
+    }
+
+    #[test]
+    fn expands_at_most_max_paths() {
+        let middle = (0..5).map(|n| H160::from_low_u64_be(n + 0x10)).collect::<Vec<_>>();
+        let last = (0..5).map(|n| H160::from_low_u64_be(n + 0x20)).collect::<Vec<_>>();
+
+        let mut parents = HashMap::from([(TOKEN, vec![]), (QUOTE, last.clone())]);
+        parents.extend(middle.iter().map(|token| (*token, vec![TOKEN])));
+        parents.extend(last.iter().map(|token| (*token, middle.clone())));
+
+        let paths = expand_paths(&parents, TOKEN, QUOTE);
+        assert_eq!(paths.len(), MAX_PATHS);
+        for path in paths {
+            assert_eq!(path.len(), 4);
+            assert_eq!((path[0], path[3]), (TOKEN, QUOTE));
+        }
+    }
+}
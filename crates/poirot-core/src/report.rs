@@ -0,0 +1,415 @@
+use crate::{
+    balances::GasDetails,
+    inspectors::{Arbitrage, JitLiquidity, LiquidationReport, PriceSource, Sandwich},
+    parser::to_h160,
+    pricing::u256_to_f64,
+    tree::TransactionTree,
+    TracingClient,
+};
+use alloy_primitives::{I256, U256};
+use reth_primitives::{H160, H256};
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// Version of the [`BlockReport`] JSON schema, bumped on any change to its fields.
+pub const REPORT_VERSION: u32 = 1;
+
+/// The results of every inspector for a block.
+#[derive(Debug, Clone, Default)]
+pub struct BlockMev {
+    pub sandwiches: Vec<Sandwich>,
+    pub arbitrages: Vec<Arbitrage>,
+    pub liquidations: Vec<LiquidationReport>,
+    pub jit: Vec<JitLiquidity>,
+}
+
+/// The MEV extracted in a block, serialized to JSON for dashboards.
+///
+/// Addresses and hashes are `0x` prefixed hex strings and token amounts are decimal strings of
+/// raw units, so they survive JSON parsers that read numbers as doubles. Values are in raw units
+/// of the quote token of the [`PriceSource`] used, and `null` when a price is missing.
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockReport {
+    pub version: u32,
+    pub block: u64,
+    pub fee_recipient: String,
+    pub transactions: usize,
+    /// Sum of the valued profits below.
+    pub total_mev: f64,
+    /// Gas used by the transactions of the MEV found.
+    pub searcher_gas_used: u64,
+    /// Wei paid for that gas.
+    pub searcher_gas_cost: String,
+    /// Wei of that gas cost paid to the fee recipient as priority fees.
+    pub searcher_priority_fees: String,
+    /// Wei sent directly to the fee recipient by each transaction, in successful calls.
+    pub coinbase_transfers: Vec<CoinbaseTransferEntry>,
+    pub sandwiches: Vec<SandwichEntry>,
+    pub arbitrages: Vec<ArbitrageEntry>,
+    pub liquidations: Vec<LiquidationEntry>,
+    pub jit: Vec<JitEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CoinbaseTransferEntry {
+    pub transaction: String,
+    pub amount: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SandwichEntry {
+    pub protocol: String,
+    pub pool: String,
+    pub searcher: String,
+    pub frontrun: String,
+    pub victims: Vec<String>,
+    pub backrun: String,
+    pub token: String,
+    pub profit: String,
+    pub value: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArbitrageEntry {
+    pub transaction: String,
+    pub searcher: String,
+    pub contract: Option<String>,
+    pub protocols: Vec<String>,
+    pub pools: Vec<String>,
+    pub token: String,
+    pub profit: String,
+    pub value: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LiquidationEntry {
+    pub transaction: String,
+    pub protocol: String,
+    pub liquidator: String,
+    pub borrower: String,
+    pub debt_asset: Option<String>,
+    pub debt_repaid: Option<String>,
+    pub collateral_asset: Option<String>,
+    pub collateral_seized: Option<String>,
+    pub value: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JitEntry {
+    pub protocol: String,
+    pub pool: String,
+    pub searcher: String,
+    pub mint: String,
+    pub victims: Vec<String>,
+    pub burn: String,
+    pub capital: [String; 2],
+    pub fees: Option<[String; 2]>,
+    pub value: Option<f64>,
+}
+
+impl BlockReport {
+    /// Builds the report of `block` from its transaction trees and the MEV found in them, reading
+    /// the fee recipient from the header and the gas of every searcher transaction from its
+    /// receipt. Transactions whose receipt can't be read are left out of the gas totals.
+    pub async fn build(
+        tracer: &TracingClient,
+        block: u64,
+        trees: &[TransactionTree],
+        mev: &BlockMev,
+        prices: &impl PriceSource,
+    ) -> eyre::Result<Self> {
+        let fee_recipient = tracer.fee_recipient(block)?;
+
+        let mut gas = vec![];
+        for hash in searcher_transactions(mev) {
+            match GasDetails::fetch(tracer, hash, block).await {
+                Ok(details) => gas.push(details),
+                Err(err) => tracing::warn!(?hash, %err, "Leaving the gas of a transaction out"),
+            }
+        }
+
+        Ok(Self::new(block, fee_recipient, trees, mev, &gas, prices))
+    }
+
+    /// Builds the report of `block` from its transaction trees, the MEV found in them and the gas
+    /// paid by the transactions of that MEV. Coinbase transfers are only classified in trees
+    /// parsed with the fee recipient, see
+    /// [`Parser::with_fee_recipient`](crate::parser::Parser::with_fee_recipient).
+    pub fn new(
+        block: u64,
+        fee_recipient: H160,
+        trees: &[TransactionTree],
+        mev: &BlockMev,
+        gas: &[GasDetails],
+        prices: &impl PriceSource,
+    ) -> Self {
+        let coinbase_transfers = trees
+            .iter()
+            .filter_map(|tree| {
+                let transfers = tree.coinbase_transfers();
+                let amount = transfers.iter().map(|transfer| transfer.amount).sum::<U256>();
+                (amount > U256::ZERO).then(|| CoinbaseTransferEntry {
+                    transaction: hex(&tree.hash),
+                    amount: amount.to_string(),
+                })
+            })
+            .collect();
+
+        let gas_used = gas.iter().map(|gas| gas.gas_used).sum();
+        let gas_cost = gas.iter().fold(U256::ZERO, |total, gas| total + gas.cost());
+        let priority_fees = gas.iter().fold(U256::ZERO, |total, gas| total + gas.priority_fees());
+
+        let sandwiches = mev
+            .sandwiches
+            .iter()
+            .map(|sandwich| SandwichEntry {
+                protocol: format!("{:?}", sandwich.protocol),
+                pool: hex(&sandwich.pool),
+                searcher: hex(&sandwich.searcher),
+                frontrun: hex(&sandwich.frontrun),
+                victims: sandwich.victims.iter().map(|victim| hex(&victim.transaction)).collect(),
+                backrun: hex(&sandwich.backrun),
+                token: hex(&sandwich.token),
+                profit: sandwich.profit.to_string(),
+                value: signed_value(prices, &sandwich.token, sandwich.profit),
+            })
+            .collect::<Vec<_>>();
+
+        let arbitrages = mev
+            .arbitrages
+            .iter()
+            .map(|arbitrage| ArbitrageEntry {
+                transaction: hex(&arbitrage.transaction),
+                searcher: hex(&arbitrage.searcher),
+                contract: arbitrage.contract.as_ref().map(hex),
+                protocols: arbitrage
+                    .protocols()
+                    .iter()
+                    .map(|protocol| format!("{protocol:?}"))
+                    .collect(),
+                pools: arbitrage.route.iter().map(|swap| hex(&swap.pool)).collect(),
+                token: hex(&arbitrage.token),
+                profit: arbitrage.profit.to_string(),
+                value: signed_value(prices, &arbitrage.token, arbitrage.profit),
+            })
+            .collect::<Vec<_>>();
+
+        let liquidations = mev
+            .liquidations
+            .iter()
+            .map(|report| {
+                let liquidation = &report.liquidation;
+                LiquidationEntry {
+                    transaction: hex(&report.transaction),
+                    protocol: format!("{:?}", liquidation.protocol),
+                    liquidator: hex(&liquidation.liquidator),
+                    borrower: hex(&to_h160(&liquidation.borrower)),
+                    debt_asset: liquidation.debt_asset.as_ref().map(hex),
+                    debt_repaid: liquidation.debt_repaid.map(|amount| amount.to_string()),
+                    collateral_asset: liquidation.collateral_asset.as_ref().map(hex),
+                    collateral_seized: liquidation
+                        .collateral_seized
+                        .map(|amount| amount.to_string()),
+                    value: report.bonus,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let jit = mev.jit.iter().map(|position| jit_entry(position, prices)).collect::<Vec<_>>();
+
+        let total_mev = sandwiches
+            .iter()
+            .map(|entry| entry.value)
+            .chain(arbitrages.iter().map(|entry| entry.value))
+            .chain(liquidations.iter().map(|entry| entry.value))
+            .chain(jit.iter().map(|entry| entry.value))
+            .flatten()
+            .sum();
+
+        Self {
+            version: REPORT_VERSION,
+            block,
+            fee_recipient: hex(&fee_recipient),
+            transactions: trees.len(),
+            total_mev,
+            searcher_gas_used: gas_used,
+            searcher_gas_cost: gas_cost.to_string(),
+            searcher_priority_fees: priority_fees.to_string(),
+            coinbase_transfers,
+            sandwiches,
+            arbitrages,
+            liquidations,
+            jit,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+fn jit_entry(position: &JitLiquidity, prices: &impl PriceSource) -> JitEntry {
+    // Uniswap V3 pools order their tokens by address
+    let tokens = position.victims.first().and_then(|(_, swap)| {
+        let (a, b) = (swap.token_in?, swap.token_out?);
+        Some(if a < b { [a, b] } else { [b, a] })
+    });
+
+    let value = position.fees.zip(tokens).and_then(|(fees, tokens)| {
+        Some(value(prices, &tokens[0], fees[0])? + value(prices, &tokens[1], fees[1])?)
+    });
+
+    JitEntry {
+        protocol: format!("{:?}", position.protocol),
+        pool: hex(&position.pool),
+        searcher: hex(&position.searcher),
+        mint: hex(&position.mint),
+        victims: position.victims.iter().map(|(transaction, _)| hex(transaction)).collect(),
+        burn: hex(&position.burn),
+        capital: position.capital.map(|amount| amount.to_string()),
+        fees: position.fees.map(|fees| fees.map(|amount| amount.to_string())),
+        value,
+    }
+}
+
+/// The transactions sent by searchers to extract the MEV found, each counted once.
+fn searcher_transactions(mev: &BlockMev) -> BTreeSet<H256> {
+    let sandwiches =
+        mev.sandwiches.iter().flat_map(|sandwich| [sandwich.frontrun, sandwich.backrun]);
+    let arbitrages = mev.arbitrages.iter().map(|arbitrage| arbitrage.transaction);
+    let liquidations = mev.liquidations.iter().map(|report| report.transaction);
+    let jit = mev.jit.iter().flat_map(|position| [position.mint, position.burn]);
+
+    sandwiches.chain(arbitrages).chain(liquidations).chain(jit).collect()
+}
+
+fn value(prices: &impl PriceSource, token: &H160, amount: U256) -> Option<f64> {
+    Some(u256_to_f64(amount) * prices.price(token)?)
+}
+
+fn signed_value(prices: &impl PriceSource, token: &H160, amount: I256) -> Option<f64> {
+    let value = value(prices, token, amount.unsigned_abs())?;
+    Some(if amount.is_negative() { -value } else { value })
+}
+
+/// Full `0x` prefixed hex, unlike the shortened `Display` of hashes.
+fn hex<T: std::fmt::Debug>(value: &T) -> String {
+    format!("{value:?}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        action::{ActionType, CoinbaseTransfer, Liquidation, Protocol},
+        inspectors::VictimLoss,
+        test_utils::tree,
+    };
+    use alloy_primitives::Address;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    const FEE_RECIPIENT: H160 = H160([1; 20]);
+    const SEARCHER: H160 = H160([2; 20]);
+    const CONTRACT: H160 = H160([3; 20]);
+    const POOL: H160 = H160([4; 20]);
+    const TOKEN: H160 = H160([5; 20]);
+    const BORROWER: H160 = H160([6; 20]);
+
+    fn mev(trees: &[TransactionTree]) -> BlockMev {
+        let sandwich = Sandwich {
+            block: 1,
+            protocol: Protocol::UniswapV2,
+            pool: POOL,
+            searcher: SEARCHER,
+            searcher_contract: Some(CONTRACT),
+            frontrun: trees[0].hash,
+            victims: vec![VictimLoss {
+                transaction: trees[1].hash,
+                sender: H160([7; 20]),
+                amount_in: U256::from(100),
+                amount_out: U256::from(90),
+                loss: U256::from(5),
+            }],
+            backrun: trees[2].hash,
+            token: TOKEN,
+            profit: I256::from_raw(U256::from(10)),
+            other_token: H160([8; 20]),
+            leftover: I256::ZERO,
+        };
+        let liquidation = LiquidationReport {
+            block: 1,
+            transaction: trees[2].hash,
+            searcher: SEARCHER,
+            contract: Some(CONTRACT),
+            liquidation: Liquidation {
+                protocol: Protocol::AaveV2,
+                pool: POOL,
+                liquidator: CONTRACT,
+                borrower: Address::from(BORROWER.0),
+                debt_asset: Some(TOKEN),
+                debt_repaid: Some(U256::from(50)),
+                collateral_asset: None,
+                collateral_seized: None,
+            },
+            bonus: Some(3.0),
+        };
+
+        BlockMev {
+            sandwiches: vec![sandwich],
+            liquidations: vec![liquidation],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn serializes_the_block_report() {
+        let mut trees =
+            (0..3).map(|index| tree(index, SEARCHER, CONTRACT, vec![])).collect::<Vec<_>>();
+        trees[2] = tree(
+            2,
+            SEARCHER,
+            CONTRACT,
+            vec![ActionType::CoinbaseTransfer(CoinbaseTransfer {
+                from: CONTRACT,
+                fee_recipient: FEE_RECIPIENT,
+                amount: U256::from(7),
+            })],
+        );
+        let gas = [
+            GasDetails { gas_used: 100, effective_gas_price: 3, priority_fee: 1 },
+            GasDetails { gas_used: 200, effective_gas_price: 2, priority_fee: 1 },
+        ];
+        let prices = HashMap::from([(TOKEN, 2.0)]);
+
+        let report = BlockReport::new(1, FEE_RECIPIENT, &trees, &mev(&trees), &gas, &prices);
+        let json: Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+
+        assert_eq!(json["version"], REPORT_VERSION);
+        assert_eq!(json["fee_recipient"], format!("{FEE_RECIPIENT:?}"));
+        assert_eq!(json["transactions"], 3);
+        assert_eq!(json["total_mev"], 23.0);
+        assert_eq!(json["searcher_gas_used"], 300);
+        assert_eq!(json["searcher_gas_cost"], "700");
+        assert_eq!(json["searcher_priority_fees"], "300");
+        assert_eq!(
+            json["coinbase_transfers"],
+            json!([{ "transaction": format!("{:?}", trees[2].hash), "amount": "7" }])
+        );
+
+        let sandwich = &json["sandwiches"][0];
+        assert_eq!(sandwich["protocol"], "UniswapV2");
+        assert_eq!(sandwich["victims"], json!([format!("{:?}", trees[1].hash)]));
+        assert_eq!(sandwich["profit"], "10");
+        assert_eq!(sandwich["value"], 20.0);
+
+        let liquidation = &json["liquidations"][0];
+        assert_eq!(liquidation["borrower"], format!("{BORROWER:?}"));
+        assert_eq!(liquidation["debt_repaid"], "50");
+        assert_eq!(liquidation["collateral_seized"], Value::Null);
+        assert_eq!(liquidation["value"], 3.0);
+
+        assert_eq!(json["arbitrages"], json!([]));
+        assert_eq!(json["jit"], json!([]));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::abi::DecodedCall;
+use alloy_dyn_abi::{DynSolType, DynSolValue};
+use alloy_primitives::keccak256;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// How well a candidate signature explains a call's calldata.
+///
+/// Ordered from best to worst so candidates can be sorted directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DecodeQuality {
+    /// Re-encoding the decoded arguments reproduces the calldata byte for byte.
+    Exact,
+    /// The arguments decode, but the calldata has trailing bytes or non-canonical padding.
+    Loose,
+}
+
+/// A successful decode of calldata against one candidate signature.
+#[derive(Debug, Clone)]
+pub struct SelectorCandidate {
+    pub quality: DecodeQuality,
+    pub call: DecodedCall,
+}
+
+/// Fallback index of 4-byte selectors to known function signatures, used for contracts we have
+/// no ABI for.
+#[derive(Debug, Clone, Default)]
+pub struct SelectorDatabase {
+    signatures: HashMap<[u8; 4], Vec<String>>,
+}
+
+impl SelectorDatabase {
+    pub fn new() -> Self {
+        Self { signatures: HashMap::new() }
+    }
+
+    /// Loads a signature dump with one entry per line, in the style of 4byte/openchain exports.
+    ///
+    /// Each line is either `<selector> <signature>` (separated by whitespace, `,` or `:`) or a
+    /// bare signature. Blank lines and lines starting with `#` are skipped, and selectors that
+    /// don't match the keccak of their signature are rejected.
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut database = Self::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+
+            let (selector, signature) =
+                match line.split_once(|c: char| c.is_whitespace() || c == ',' || c == ':') {
+                    Some((selector, signature)) if !selector.contains('(') => {
+                        (Some(selector), signature.trim())
+                    }
+                    _ => (None, line),
+                };
+
+            let computed = signature_selector(signature);
+            if let Some(selector) = selector {
+                let selector = selector.strip_prefix("0x").unwrap_or(selector);
+                if !hex::decode(selector).map_or(false, |bytes| bytes[..] == computed[..]) {
+                    continue
+                }
+            }
+
+            database.insert(signature.to_string());
+        }
+
+        Ok(database)
+    }
+
+    /// Adds a signature such as `transfer(address,uint256)` under its selector.
+    pub fn insert(&mut self, signature: String) {
+        let candidates = self.signatures.entry(signature_selector(&signature)).or_default();
+        if !candidates.contains(&signature) {
+            candidates.push(signature);
+        }
+    }
+
+    pub fn candidates(&self, selector: &[u8; 4]) -> &[String] {
+        self.signatures.get(selector).map_or(&[], |candidates| candidates.as_slice())
+    }
+
+    /// Tries every signature registered for the calldata's selector and returns the ones that
+    /// decode, best match first.
+    pub fn decode(&self, input: &[u8]) -> Vec<SelectorCandidate> {
+        if input.len() < 4 {
+            return vec![]
+        }
+
+        let selector: [u8; 4] = input[..4].try_into().unwrap();
+        let data = &input[4..];
+
+        let mut decoded: Vec<SelectorCandidate> = self
+            .candidates(&selector)
+            .iter()
+            .filter_map(|signature| decode_signature(signature, data))
+            .collect();
+
+        decoded.sort_by_key(|candidate| candidate.quality);
+        decoded
+    }
+}
+
+/// Computes the 4-byte selector of a function signature.
+pub fn signature_selector(signature: &str) -> [u8; 4] {
+    keccak256(signature.as_bytes())[..4].try_into().unwrap()
+}
+
+fn decode_signature(signature: &str, data: &[u8]) -> Option<SelectorCandidate> {
+    let open = signature.find('(')?;
+    let ty: DynSolType = signature[open..].parse().ok()?;
+
+    let decoded = ty.decode_params(data).ok()?;
+
+    let quality = if decoded.encode_params() == data {
+        DecodeQuality::Exact
+    } else if data.len() % 32 == 0 {
+        DecodeQuality::Loose
+    } else {
+        return None
+    };
+
+    let params = match decoded {
+        DynSolValue::Tuple(values) => values,
+        value => vec![value],
+    };
+
+    Some(SelectorCandidate {
+        quality,
+        call: DecodedCall {
+            function_name: signature[..open].to_string(),
+            signature: signature.to_string(),
+            param_names: vec![String::new(); params.len()],
+            params,
+            outputs: None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{address_word, word};
+    use alloy_primitives::{Address, U256};
+    use reth_primitives::H160;
+
+    const TRANSFER: &str = "transfer(address,uint256)";
+    const RECIPIENT: H160 = H160([2; 20]);
+
+    fn transfer_input() -> Vec<u8> {
+        [&signature_selector(TRANSFER)[..], &address_word(RECIPIENT)[..], &word(100)[..]].concat()
+    }
+
+    #[test]
+    fn computes_selectors() {
+        assert_eq!(signature_selector(TRANSFER), [0xa9, 0x05, 0x9c, 0xbb]);
+    }
+
+    #[test]
+    fn loads_signature_dumps() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/selectors.txt");
+        let database = SelectorDatabase::from_file(&path).unwrap();
+
+        assert_eq!(database.candidates(&[0xa9, 0x05, 0x9c, 0xbb]), [TRANSFER]);
+        assert_eq!(database.candidates(&[0x09, 0x5e, 0xa7, 0xb3]), ["approve(address,uint256)"]);
+        assert_eq!(database.candidates(&signature_selector("balanceOf(address)")).len(), 1);
+        // Listed under a selector that isn't its own
+        assert!(database.candidates(&signature_selector("totalSupply()")).is_empty());
+        assert!(database.candidates(&[0xde, 0xad, 0xbe, 0xef]).is_empty());
+    }
+
+    #[test]
+    fn decodes_exact_and_loose_matches() {
+        let mut database = SelectorDatabase::new();
+        database.insert(TRANSFER.to_string());
+        database.insert(TRANSFER.to_string());
+        assert_eq!(database.candidates(&signature_selector(TRANSFER)).len(), 1);
+
+        let exact = database.decode(&transfer_input());
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact[0].quality, DecodeQuality::Exact);
+        assert_eq!(exact[0].call.function_name, "transfer");
+        assert_eq!(exact[0].call.params[0], DynSolValue::Address(Address::from(RECIPIENT.0)));
+        assert_eq!(exact[0].call.params[1], DynSolValue::Uint(U256::from(100), 256));
+
+        let trailing_word = [transfer_input(), word(0).to_vec()].concat();
+        let loose = database.decode(&trailing_word);
+        assert_eq!(loose.len(), 1);
+        assert_eq!(loose[0].quality, DecodeQuality::Loose);
+
+        let trailing_byte = [transfer_input(), vec![0]].concat();
+        assert!(database.decode(&trailing_byte).is_empty());
+        assert!(database.decode(&transfer_input()[..3]).is_empty());
+    }
+}
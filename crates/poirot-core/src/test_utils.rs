@@ -0,0 +1,167 @@
+//! Fixtures shared by the unit tests.
+
+use crate::{
+    action::{self, ActionType, Protocol, Swap, Transfer},
+    classifier::{ContractClassifier, ContractKind},
+    parser::{Parser, IERC20},
+    pools::{PoolInfo, PoolRegistry},
+    tree::{ActionNode, TransactionTree},
+};
+use alloy_primitives::Address;
+use alloy_sol_types::SolCall;
+use reth_primitives::{H160, H256, U256};
+use reth_rpc_types::trace::parity::{
+    Action, CallAction, CallOutput, CallType, LocalizedTransactionTrace, TraceOutput,
+    TransactionTrace,
+};
+use std::sync::Arc;
+
+/// Encodes `value` as a 32 byte ABI word, sign extending negative values.
+pub fn word(value: i128) -> [u8; 32] {
+    let mut word = if value < 0 { [0xff; 32] } else { [0; 32] };
+    word[16..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+/// Encodes `address` as a left padded 32 byte ABI word.
+pub fn address_word(address: H160) -> [u8; 32] {
+    let mut word = [0; 32];
+    word[12..].copy_from_slice(&address.0);
+    word
+}
+
+/// `transfer(to, amount)` calldata of an ERC20 token.
+pub fn transfer_input(to: H160, amount: i128) -> Vec<u8> {
+    [&IERC20::transferCall::SELECTOR[..], &address_word(to)[..], &word(amount)[..]].concat()
+}
+
+/// A successful top level call from `from` to `to`, in the first transaction of block 1.
+pub fn call_trace(from: H160, to: H160, input: Vec<u8>) -> LocalizedTransactionTrace {
+    LocalizedTransactionTrace {
+        trace: TransactionTrace {
+            action: Action::Call(CallAction {
+                from,
+                call_type: CallType::Call,
+                gas: Default::default(),
+                input: input.into(),
+                to,
+                value: U256::ZERO,
+            }),
+            error: None,
+            result: Some(TraceOutput::Call(CallOutput {
+                gas_used: Default::default(),
+                output: Default::default(),
+            })),
+            subtraces: 0,
+            trace_address: vec![],
+        },
+        block_hash: Some(H256::zero()),
+        block_number: Some(1),
+        transaction_hash: Some(H256::zero()),
+        transaction_position: Some(0),
+    }
+}
+
+/// Sets the return data of a successful call trace.
+pub fn returning(
+    mut trace: LocalizedTransactionTrace,
+    output: Vec<u8>,
+) -> LocalizedTransactionTrace {
+    trace.trace.result =
+        Some(TraceOutput::Call(CallOutput { gas_used: Default::default(), output: output.into() }));
+    trace
+}
+
+/// Marks a call trace as reverted, dropping its result.
+pub fn reverted(mut trace: LocalizedTransactionTrace) -> LocalizedTransactionTrace {
+    trace.trace.error = Some("Reverted".to_string());
+    trace.trace.result = None;
+    trace
+}
+
+/// Places a call trace at `trace_address` in transaction `tx_index`, making `subtraces` calls.
+pub fn at(
+    mut trace: LocalizedTransactionTrace,
+    tx_index: u64,
+    trace_address: Vec<usize>,
+    subtraces: usize,
+) -> LocalizedTransactionTrace {
+    trace.transaction_hash = Some(H256::from_low_u64_be(tx_index));
+    trace.transaction_position = Some(tx_index);
+    trace.trace.trace_address = trace_address;
+    trace.trace.subtraces = subtraces;
+    trace
+}
+
+/// A pool of `protocol` trading `tokens`.
+pub fn pool(protocol: Protocol, tokens: &[H160]) -> PoolInfo {
+    PoolInfo {
+        protocol,
+        tokens: tokens.to_vec(),
+        underlying_tokens: vec![],
+        fee: None,
+        factory: None,
+        created_at: None,
+    }
+}
+
+/// A parser over `block_trace` that knows the given contracts and pools.
+pub fn parser(
+    block_trace: Vec<LocalizedTransactionTrace>,
+    contracts: &[(H160, ContractKind)],
+    pools: &[(H160, PoolInfo)],
+) -> Parser {
+    let mut classifier = ContractClassifier::new();
+    for (address, kind) in contracts {
+        classifier.insert(*address, *kind);
+    }
+
+    let mut registry = PoolRegistry::new();
+    for (address, info) in pools {
+        registry.insert(*address, info.clone());
+    }
+
+    Parser::new(block_trace, Arc::new(classifier), Arc::new(registry))
+}
+
+/// A transfer of `amount` of `token` from `from` to `to`.
+pub fn transfer(from: H160, to: H160, token: H160, amount: u64) -> Transfer {
+    let amount = alloy_primitives::U256::from(amount);
+    Transfer::new(Address::from(from.0), Address::from(to.0), amount, token)
+}
+
+/// A swap of `amount_in` of `token_in` for `amount_out` of `token_out` on the UniswapV2 pair
+/// `pool`.
+pub fn swap(pool: H160, token_in: H160, token_out: H160, amount_in: u64, amount_out: u64) -> Swap {
+    Swap::new(
+        Protocol::UniswapV2,
+        pool,
+        H160::zero(),
+        Address::ZERO,
+        Some(token_in),
+        Some(token_out),
+        Some(alloy_primitives::U256::from(amount_in)),
+        Some(alloy_primitives::U256::from(amount_out)),
+    )
+}
+
+/// Transaction `tx_index` of block 1, sent by `from` to `to`, whose top level call made
+/// `actions` in order.
+pub fn tree(tx_index: usize, from: H160, to: H160, actions: Vec<ActionType>) -> TransactionTree {
+    let trace = at(call_trace(from, to, vec![]), tx_index as u64, vec![], actions.len());
+    let children = actions
+        .into_iter()
+        .enumerate()
+        .map(|(index, ty)| ActionNode::new(action::Action::new(ty, &trace), vec![index], vec![]))
+        .collect();
+    let root = action::Action::new(ActionType::Unclassified(trace.clone()), &trace);
+
+    TransactionTree {
+        hash: H256::from_low_u64_be(tx_index as u64),
+        block: 1,
+        tx_index,
+        from,
+        to: Some(to),
+        root: ActionNode::new(root, vec![], children),
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{abi::parse_address, TracingClient};
+use alloy_primitives::U256;
+use alloy_sol_types::{sol, SolCall};
+use reth_primitives::H160;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+sol! {
+    interface IERC20Metadata {
+        function name() external view returns (string);
+        function symbol() external view returns (string);
+        function decimals() external view returns (uint8);
+    }
+}
+
+sol! {
+    /// Metadata getters of tokens predating the ERC20 metadata extension, e.g. MKR.
+    interface IERC20MetadataBytes32 {
+        function name() external view returns (bytes32);
+        function symbol() external view returns (bytes32);
+    }
+}
+
+/// ERC20 metadata of a token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenInfo {
+    pub address: H160,
+    pub decimals: u8,
+    /// Empty if the token doesn't implement `symbol()`.
+    pub symbol: String,
+    /// Empty if the token doesn't implement `name()`.
+    pub name: String,
+}
+
+impl TokenInfo {
+    /// Formats a raw amount of the token with its decimals and symbol, e.g. `1.5 WETH`.
+    pub fn format_amount(&self, amount: U256) -> String {
+        let amount = format_units(amount, self.decimals);
+        if self.symbol.is_empty() {
+            return format!("{amount} {:?}", self.address)
+        }
+
+        format!("{amount} {}", self.symbol)
+    }
+}
+
+/// Token address → [`TokenInfo`] lookup, resolved with `eth_call`s through a [`TracingClient`]
+/// and optionally cached on disk.
+#[derive(Debug, Default)]
+pub struct TokenMetadata {
+    tokens: HashMap<H160, TokenInfo>,
+    cache: Option<PathBuf>,
+}
+
+impl TokenMetadata {
+    pub fn new() -> Self {
+        Self { tokens: HashMap::new(), cache: None }
+    }
+
+    /// Loads the tokens cached at `path`, if the file exists, and appends every token resolved
+    /// from now on to it. The cache has one `<address>\t<decimals>\t<symbol>\t<name>` entry per
+    /// line.
+    pub fn with_cache(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut metadata = Self { tokens: HashMap::new(), cache: Some(path.to_path_buf()) };
+        if !path.exists() {
+            return Ok(metadata)
+        }
+
+        let reader = BufReader::new(File::open(path)?);
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue
+            }
+
+            let info = parse_cache_line(&line)
+                .ok_or_else(|| format!("Invalid token cache entry {line}"))?;
+            metadata.tokens.insert(info.address, info);
+        }
+
+        Ok(metadata)
+    }
+
+    pub fn get(&self, token: &H160) -> Option<&TokenInfo> {
+        self.tokens.get(token)
+    }
+
+    pub fn insert(&mut self, info: TokenInfo) {
+        self.tokens.insert(info.address, info);
+    }
+
+    /// Returns the metadata of `token`, reading it from the state at `block` if it isn't known
+    /// yet. Fails if the token doesn't implement `decimals()`.
+    pub async fn resolve(
+        &mut self,
+        tracer: &TracingClient,
+        token: H160,
+        block: u64,
+    ) -> eyre::Result<&TokenInfo> {
+        if !self.tokens.contains_key(&token) {
+            let info = fetch_token_info(tracer, token, block).await?;
+            self.append_to_cache(&info)?;
+            self.insert(info);
+        }
+
+        Ok(&self.tokens[&token])
+    }
+
+    /// Formats a raw amount of `token`, falling back to the raw amount and the token address if
+    /// the token isn't known.
+    pub fn format_amount(&self, token: &H160, amount: U256) -> String {
+        match self.get(token) {
+            Some(info) => info.format_amount(amount),
+            None => format!("{amount} {token:?}"),
+        }
+    }
+
+    fn append_to_cache(&self, info: &TokenInfo) -> eyre::Result<()> {
+        let Some(path) = &self.cache else { return Ok(()) };
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(
+            file,
+            "{:?}\t{}\t{}\t{}",
+            info.address,
+            info.decimals,
+            sanitize(&info.symbol),
+            sanitize(&info.name)
+        )?;
+
+        Ok(())
+    }
+}
+
+async fn fetch_token_info(
+    tracer: &TracingClient,
+    token: H160,
+    block: u64,
+) -> eyre::Result<TokenInfo> {
+    let output = tracer.eth_call(token, IERC20Metadata::decimalsCall {}.encode(), block).await?;
+    let decimals = IERC20Metadata::decimalsCall::decode_returns(&output, true)
+        .map_err(|err| eyre::eyre!("{token:?} has no decimals: {err}"))?
+        ._0;
+
+    let symbol = fetch_text(
+        tracer,
+        token,
+        block,
+        IERC20Metadata::symbolCall {}.encode(),
+        |output| {
+            IERC20Metadata::symbolCall::decode_returns(output, true).ok().map(|symbol| symbol._0)
+        },
+        |output| {
+            IERC20MetadataBytes32::symbolCall::decode_returns(output, true)
+                .ok()
+                .map(|symbol| bytes32_to_string(&symbol._0[..]))
+        },
+    )
+    .await;
+
+    let name = fetch_text(
+        tracer,
+        token,
+        block,
+        IERC20Metadata::nameCall {}.encode(),
+        |output| IERC20Metadata::nameCall::decode_returns(output, true).ok().map(|name| name._0),
+        |output| {
+            IERC20MetadataBytes32::nameCall::decode_returns(output, true)
+                .ok()
+                .map(|name| bytes32_to_string(&name._0[..]))
+        },
+    )
+    .await;
+
+    Ok(TokenInfo { address: token, decimals, symbol, name })
+}
+
+/// Calls a `name()` or `symbol()` getter, decoding its return as a `string` and falling back to
+/// a `bytes32`. Both getters are optional in ERC20, so failures resolve to an empty string.
+async fn fetch_text(
+    tracer: &TracingClient,
+    token: H160,
+    block: u64,
+    input: Vec<u8>,
+    as_string: impl Fn(&[u8]) -> Option<String>,
+    as_bytes32: impl Fn(&[u8]) -> Option<String>,
+) -> String {
+    let Ok(output) = tracer.eth_call(token, input, block).await else { return String::new() };
+
+    as_string(&output[..]).or_else(|| as_bytes32(&output[..])).unwrap_or_default()
+}
+
+/// Decodes a right-padded `bytes32` string.
+fn bytes32_to_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// Formats `amount` as a decimal number with `decimals` fractional digits, dropping trailing
+/// zeros.
+pub fn format_units(amount: U256, decimals: u8) -> String {
+    let unit = U256::from(10).pow(U256::from(decimals));
+    let integer = amount / unit;
+    let fraction = amount % unit;
+
+    if fraction.is_zero() {
+        return integer.to_string()
+    }
+
+    let fraction = format!("{:0>width$}", fraction.to_string(), width = decimals as usize);
+    format!("{integer}.{}", fraction.trim_end_matches('0'))
+}
+
+/// Keeps cache entries on a single tab-separated line.
+fn sanitize(text: &str) -> String {
+    text.replace(['\t', '\n', '\r'], " ")
+}
+
+fn parse_cache_line(line: &str) -> Option<TokenInfo> {
+    let mut fields = line.split('\t');
+
+    Some(TokenInfo {
+        address: parse_address(fields.next()?)?,
+        decimals: fields.next()?.parse().ok()?,
+        symbol: fields.next()?.to_string(),
+        name: fields.next()?.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: H160 = H160([1; 20]);
+
+    #[test]
+    fn formats_units() {
+        assert_eq!(format_units(U256::from(1_500_000), 6), "1.5");
+        assert_eq!(format_units(U256::from(2_000_000), 6), "2");
+        assert_eq!(format_units(U256::from(1234), 0), "1234");
+        assert_eq!(format_units(U256::from(5), 6), "0.000005");
+        assert_eq!(format_units(U256::ZERO, 18), "0");
+    }
+
+    #[test]
+    fn formats_amounts_with_the_symbol_or_address() {
+        let mut info =
+            TokenInfo { address: TOKEN, decimals: 2, symbol: "USD".into(), name: String::new() };
+        assert_eq!(info.format_amount(U256::from(150)), "1.5 USD");
+
+        info.symbol.clear();
+        assert_eq!(info.format_amount(U256::from(150)), format!("1.5 {TOKEN:?}"));
+    }
+
+    #[test]
+    fn reads_zero_padded_bytes32_strings() {
+        let mut bytes = [0; 32];
+        bytes[..3].copy_from_slice(b"MKR");
+        assert_eq!(bytes32_to_string(&bytes), "MKR");
+
+        assert_eq!(bytes32_to_string(&[0; 32]), "");
+        assert_eq!(bytes32_to_string(&[b'A'; 32]), "A".repeat(32));
+
+        bytes[1] = 0xff;
+        assert_eq!(bytes32_to_string(&bytes), "M\u{fffd}R");
+    }
+
+    #[test]
+    fn parses_cache_lines() {
+        let line = format!("{TOKEN:?}\t18\tWETH\tWrapped Ether");
+        let info = parse_cache_line(&line).unwrap();
+        assert_eq!(info.decimals, 18);
+        assert_eq!(info.symbol, "WETH");
+        assert_eq!(info.name, "Wrapped Ether");
+
+        assert_eq!(parse_cache_line(&format!("{TOKEN:?}\t18\tWETH")), None);
+        assert_eq!(parse_cache_line(&format!("{TOKEN:?}\teighteen\tWETH\tWrapped Ether")), None);
+        assert_eq!(sanitize("Wrapped\tEther\n"), "Wrapped Ether ");
+    }
+}
@@ -0,0 +1,451 @@
+use crate::{
+    action::{Action, ActionType, CoinbaseTransfer, Liquidation, Swap, Transfer},
+    parser::{
+        IFlashLoanRecipient, IUniswapV2Callee, IUniswapV3FlashCallback, IUniswapV3MintCallback,
+        IUniswapV3SwapCallback,
+    },
+};
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::SolCall;
+use reth_primitives::{H160, H256};
+use reth_rpc_types::trace::parity::Action as RethAction;
+
+/// The classified call tree of a single transaction.
+#[derive(Debug, Clone)]
+pub struct TransactionTree {
+    pub hash: H256,
+    pub block: u64,
+    pub tx_index: usize,
+    /// The account that sent the transaction.
+    pub from: H160,
+    /// The contract called by the transaction, `None` for contract creations.
+    pub to: Option<H160>,
+    pub root: ActionNode,
+}
+
+/// A call in a transaction, together with the calls it made.
+#[derive(Debug, Clone)]
+pub struct ActionNode {
+    pub action: Action,
+    pub trace_address: Vec<usize>,
+    /// Sub-calls that are part of this action rather than actions of their own, e.g. the token
+    /// transfers and callback of a swap.
+    pub absorbed: Vec<ActionNode>,
+    pub children: Vec<ActionNode>,
+    /// Ether moved by the call itself, whatever its action.
+    pub value: Option<EthTransfer>,
+    /// Whether the call reverted, undoing everything in its subtree.
+    pub reverted: bool,
+}
+
+/// Ether sent with a call or contract creation, or the balance of a self-destructed contract.
+#[derive(Debug, Clone)]
+pub struct EthTransfer {
+    pub from: H160,
+    pub to: H160,
+    pub amount: U256,
+}
+
+impl TransactionTree {
+    /// Returns the actions of the transaction in call order, skipping absorbed sub-calls and
+    /// everything done in reverted calls.
+    pub fn actions(&self) -> Vec<&Action> {
+        let mut actions = vec![];
+        self.root.collect(&mut actions);
+        actions
+    }
+
+    /// Returns the pool swaps of the transaction in call order.
+    pub fn swaps(&self) -> Vec<&Swap> {
+        self.actions().into_iter().flat_map(Action::swaps).collect()
+    }
+
+    /// Returns the ether the transaction paid to the block's fee recipient, in call order.
+    pub fn coinbase_transfers(&self) -> Vec<&CoinbaseTransfer> {
+        self.actions()
+            .into_iter()
+            .filter_map(|action| match &action.ty {
+                ActionType::CoinbaseTransfer(transfer) => Some(transfer),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the liquidations of the transaction in call order.
+    pub fn liquidations(&self) -> Vec<&Liquidation> {
+        self.actions().into_iter().flat_map(Action::liquidations).collect()
+    }
+
+    /// Completes the tokens and amounts of the transaction's swaps from its token transfers: the
+    /// output is what the pool sends during the swap, the input what it is paid in the swap
+    /// callback or, for pools like UniswapV2 pairs, right before the swap by the contract calling
+    /// it. Transfers made in reverted calls are ignored.
+    pub fn fill_swaps(&mut self) {
+        let mut transfers = vec![];
+        self.root.collect_transfers(&mut transfers);
+        transfers.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        self.root.fill_swaps(&transfers);
+    }
+
+    /// Completes the amounts of the transaction's liquidations from the token transfers made
+    /// during them: the debt repaid by the liquidator and the collateral it receives.
+    pub fn fill_liquidations(&mut self) {
+        let mut transfers = vec![];
+        self.root.collect_transfers(&mut transfers);
+        transfers.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        self.root.fill_liquidations(&transfers);
+    }
+}
+
+impl ActionNode {
+    pub fn new(action: Action, trace_address: Vec<usize>, children: Vec<ActionNode>) -> Self {
+        let mut node = Self {
+            action,
+            trace_address,
+            absorbed: vec![],
+            children,
+            value: None,
+            reverted: false,
+        };
+        node.absorb_children();
+        node
+    }
+
+    fn collect<'a>(&'a self, actions: &mut Vec<&'a Action>) {
+        if self.reverted {
+            return
+        }
+
+        actions.push(&self.action);
+        self.children.iter().for_each(|child| child.collect(actions));
+    }
+
+    fn collect_transfers(&self, transfers: &mut Vec<(Vec<usize>, Transfer)>) {
+        // Nothing a reverted call did moved any tokens
+        if self.reverted {
+            return
+        }
+
+        if let ActionType::Transfer(transfer) = &self.action.ty {
+            transfers.push((self.trace_address.clone(), transfer.clone()));
+        }
+
+        self.absorbed
+            .iter()
+            .chain(&self.children)
+            .for_each(|node| node.collect_transfers(transfers));
+    }
+
+    fn fill_swaps(&mut self, transfers: &[(Vec<usize>, Transfer)]) {
+        match &mut self.action.ty {
+            ActionType::Swap(swap) => fill_swap(swap, &self.trace_address, transfers),
+            ActionType::BatchSwap(swaps) => {
+                swaps.iter_mut().for_each(|swap| fill_swap(swap, &self.trace_address, transfers))
+            }
+            _ => {}
+        }
+
+        self.children.iter_mut().for_each(|child| child.fill_swaps(transfers));
+    }
+
+    fn fill_liquidations(&mut self, transfers: &[(Vec<usize>, Transfer)]) {
+        match &mut self.action.ty {
+            ActionType::Liquidation(liquidation) => {
+                fill_liquidation(liquidation, &self.trace_address, transfers)
+            }
+            ActionType::BatchLiquidation(liquidations) => {
+                for liquidation in liquidations {
+                    fill_liquidation(liquidation, &self.trace_address, transfers)
+                }
+            }
+            _ => {}
+        }
+
+        self.children.iter_mut().for_each(|child| child.fill_liquidations(transfers));
+    }
+
+    /// Moves the transfers and callbacks made by a classified action into
+    /// [`ActionNode::absorbed`]. Anything else found inside a callback (e.g. the arbitrage
+    /// executed in a flash swap) is kept as a child so it is still reported.
+    fn absorb_children(&mut self) {
+        if matches!(self.action.ty, ActionType::Unclassified(_)) {
+            return
+        }
+
+        for mut child in std::mem::take(&mut self.children) {
+            if matches!(child.action.ty, ActionType::Transfer(_)) {
+                self.absorbed.push(child);
+            } else if is_callback(&child.action) {
+                for grandchild in std::mem::take(&mut child.children) {
+                    if matches!(grandchild.action.ty, ActionType::Transfer(_)) {
+                        child.absorbed.push(grandchild);
+                    } else {
+                        self.children.push(grandchild);
+                    }
+                }
+                self.absorbed.push(child);
+            } else {
+                self.children.push(child);
+            }
+        }
+    }
+}
+
+/// Fills in the missing tokens and amounts of the swap made by the call at `trace_address`, given
+/// every transfer of the transaction in call order.
+fn fill_swap(swap: &mut Swap, trace_address: &[usize], transfers: &[(Vec<usize>, Transfer)]) {
+    let pool = Address::from(swap.pool.0);
+    let inside = |address: &Vec<usize>| address.starts_with(trace_address);
+
+    if swap.token_out.is_none() || swap.amount_out.is_none() {
+        let sent = transfers.iter().find(|(address, transfer)| {
+            inside(address) &&
+                transfer.from == pool &&
+                swap.token_out.map_or(true, |token| token == transfer.token)
+        });
+
+        if let Some((_, sent)) = sent {
+            swap.token_out = Some(sent.token);
+            swap.amount_out.get_or_insert(sent.amount);
+        }
+    }
+
+    if swap.token_in.is_none() || swap.amount_in.is_none() {
+        let pays_pool = |transfer: &Transfer| {
+            transfer.to == pool &&
+                swap.token_in.map_or(swap.token_out != Some(transfer.token), |token| {
+                    token == transfer.token
+                })
+        };
+
+        // Pools like UniswapV2 pairs are paid before the swap, by the contract calling it
+        let caller = trace_address.split_last().map(|(_, caller)| caller);
+        let paid_by_caller = |address: &Vec<usize>| {
+            caller.is_some_and(|caller| address.starts_with(caller)) && &address[..] < trace_address
+        };
+
+        let paid = transfers
+            .iter()
+            .find(|(address, transfer)| inside(address) && pays_pool(transfer))
+            .or_else(|| {
+                transfers
+                    .iter()
+                    .rev()
+                    .find(|(address, transfer)| paid_by_caller(address) && pays_pool(transfer))
+            });
+
+        if let Some((_, paid)) = paid {
+            swap.token_in = Some(paid.token);
+            swap.amount_in.get_or_insert(paid.amount);
+        }
+    }
+}
+
+/// Fills in the missing amounts of the liquidation made by the call at `trace_address`, given
+/// every transfer of the transaction in call order. Only transfers made inside the call count.
+fn fill_liquidation(
+    liquidation: &mut Liquidation,
+    trace_address: &[usize],
+    transfers: &[(Vec<usize>, Transfer)],
+) {
+    let liquidator = Address::from(liquidation.liquidator.0);
+    let inside = transfers
+        .iter()
+        .filter(|(address, _)| address.starts_with(trace_address))
+        .map(|(_, transfer)| transfer);
+
+    if liquidation.debt_repaid.is_none() {
+        let repaid = inside.clone().filter(|transfer| transfer.from == liquidator);
+        // Without a known debt asset, the first token the liquidator pays is the debt
+        let debt_asset =
+            liquidation.debt_asset.or_else(|| repaid.clone().next().map(|transfer| transfer.token));
+
+        if let Some(debt_asset) = debt_asset {
+            let amounts = repaid
+                .filter(|transfer| transfer.token == debt_asset)
+                .map(|transfer| transfer.amount)
+                .collect::<Vec<_>>();
+
+            if !amounts.is_empty() {
+                liquidation.debt_asset = Some(debt_asset);
+                liquidation.debt_repaid = Some(amounts.into_iter().sum());
+            }
+        }
+    }
+
+    if liquidation.collateral_seized.is_none() {
+        let Some(collateral_asset) = liquidation.collateral_asset else { return };
+
+        let amounts = inside
+            .filter(|transfer| transfer.to == liquidator && transfer.token == collateral_asset)
+            .map(|transfer| transfer.amount)
+            .collect::<Vec<_>>();
+
+        if !amounts.is_empty() {
+            liquidation.collateral_seized = Some(amounts.into_iter().sum());
+        }
+    }
+}
+
+/// Whether `child` is the trace address of a direct sub-call of the call at `parent`.
+pub fn is_sub_call(parent: &[usize], child: &[usize]) -> bool {
+    child.len() == parent.len() + 1 && child.starts_with(parent)
+}
+
+/// Whether the action is an unclassified call to one of the swap, mint or flash loan callbacks.
+fn is_callback(action: &Action) -> bool {
+    let ActionType::Unclassified(trace) = &action.ty else { return false };
+    let RethAction::Call(call) = &trace.trace.action else { return false };
+
+    call.input.len() >= 4 &&
+        [
+            IUniswapV2Callee::uniswapV2CallCall::SELECTOR,
+            IUniswapV3SwapCallback::uniswapV3SwapCallbackCall::SELECTOR,
+            IUniswapV3MintCallback::uniswapV3MintCallbackCall::SELECTOR,
+            IUniswapV3FlashCallback::uniswapV3FlashCallbackCall::SELECTOR,
+            IFlashLoanRecipient::receiveFlashLoanCall::SELECTOR,
+        ]
+        .iter()
+        .any(|selector| call.input[..4] == selector[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{action::Protocol, test_utils::transfer};
+    use alloy_primitives::U256;
+    use reth_primitives::H160;
+
+    const POOL: H160 = H160([1; 20]);
+    const TRADER: H160 = H160([2; 20]);
+    const TOKEN_A: H160 = H160([3; 20]);
+    const TOKEN_B: H160 = H160([4; 20]);
+    const OTHER: H160 = H160([5; 20]);
+
+    fn swap() -> Swap {
+        Swap::new(
+            Protocol::UniswapV2,
+            POOL,
+            TRADER,
+            Address::from(TRADER.0),
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn node(ty: ActionType, trace_address: Vec<usize>, children: Vec<ActionNode>) -> ActionNode {
+        ActionNode::new(Action { ty, hash: H256::zero(), block: 0 }, trace_address, children)
+    }
+
+    fn tree(root: ActionNode) -> TransactionTree {
+        TransactionTree { hash: H256::zero(), block: 0, tx_index: 0, from: TRADER, to: None, root }
+    }
+
+    #[test]
+    fn finds_direct_sub_calls() {
+        assert!(is_sub_call(&[], &[0]));
+        assert!(is_sub_call(&[0, 1], &[0, 1, 3]));
+        assert!(!is_sub_call(&[0, 1], &[0, 1, 3, 0]));
+        assert!(!is_sub_call(&[0, 1], &[0, 2, 0]));
+        assert!(!is_sub_call(&[0], &[0]));
+    }
+
+    #[test]
+    fn skips_actions_of_reverted_calls() {
+        let inner =
+            node(ActionType::Transfer(transfer(POOL, TRADER, TOKEN_A, 7)), vec![0, 0], vec![]);
+        let mut reverted = node(ActionType::Swap(swap()), vec![0], vec![inner]);
+        reverted.reverted = true;
+        let sent = node(ActionType::Transfer(transfer(TRADER, POOL, TOKEN_B, 5)), vec![1], vec![]);
+        let root = node(
+            ActionType::Transfer(transfer(TRADER, OTHER, TOKEN_B, 1)),
+            vec![],
+            vec![reverted, sent],
+        );
+
+        let tree = tree(root);
+        let actions = tree.actions();
+        assert_eq!(actions.len(), 2);
+        let ActionType::Transfer(sent) = &actions[1].ty else { panic!("expected a transfer") };
+        assert_eq!(sent.token, TOKEN_B);
+        assert_eq!(sent.amount, U256::from(5));
+    }
+
+    #[test]
+    fn fills_swap_from_transfers_inside_the_call() {
+        let mut swap = swap();
+        let transfers = vec![
+            (vec![0, 0], transfer(POOL, TRADER, TOKEN_B, 5)),
+            (vec![0, 1, 0], transfer(TRADER, POOL, TOKEN_A, 10)),
+        ];
+
+        fill_swap(&mut swap, &[0], &transfers);
+
+        assert_eq!(swap.token_out, Some(TOKEN_B));
+        assert_eq!(swap.amount_out, Some(U256::from(5)));
+        assert_eq!(swap.token_in, Some(TOKEN_A));
+        assert_eq!(swap.amount_in, Some(U256::from(10)));
+    }
+
+    #[test]
+    fn keeps_decoded_amounts() {
+        let mut swap = swap();
+        swap.amount_out = Some(U256::from(4));
+        let transfers = vec![(vec![0, 0], transfer(POOL, TRADER, TOKEN_B, 5))];
+
+        fill_swap(&mut swap, &[0], &transfers);
+
+        assert_eq!(swap.token_out, Some(TOKEN_B));
+        assert_eq!(swap.amount_out, Some(U256::from(4)));
+    }
+
+    #[test]
+    fn fills_swap_input_paid_before_the_call_by_its_caller() {
+        let mut swap = swap();
+        let transfers = vec![
+            (vec![0, 0], transfer(TRADER, POOL, TOKEN_A, 10)),
+            (vec![0, 1, 0], transfer(POOL, TRADER, TOKEN_B, 5)),
+        ];
+
+        fill_swap(&mut swap, &[0, 1], &transfers);
+
+        assert_eq!(swap.token_in, Some(TOKEN_A));
+        assert_eq!(swap.amount_in, Some(U256::from(10)));
+    }
+
+    #[test]
+    fn ignores_payments_before_the_call_by_other_callers() {
+        let mut swap = swap();
+        let transfers = vec![
+            (vec![0, 0], transfer(OTHER, POOL, TOKEN_A, 10)),
+            (vec![1, 0, 0], transfer(POOL, TRADER, TOKEN_B, 5)),
+        ];
+
+        fill_swap(&mut swap, &[1, 0], &transfers);
+
+        assert_eq!(swap.token_out, Some(TOKEN_B));
+        assert_eq!(swap.token_in, None);
+        assert_eq!(swap.amount_in, None);
+    }
+
+    #[test]
+    fn ignores_transfers_in_reverted_calls() {
+        let mut reverted =
+            node(ActionType::Transfer(transfer(POOL, TRADER, TOKEN_A, 7)), vec![0, 0], vec![]);
+        reverted.reverted = true;
+        let sent =
+            node(ActionType::Transfer(transfer(POOL, TRADER, TOKEN_B, 5)), vec![0, 1], vec![]);
+        let root = node(ActionType::Swap(swap()), vec![0], vec![reverted, sent]);
+
+        let mut tree = tree(root);
+        tree.fill_swaps();
+
+        let swaps = tree.swaps();
+        assert_eq!(swaps[0].token_out, Some(TOKEN_B));
+        assert_eq!(swaps[0].amount_out, Some(U256::from(5)));
+    }
+}